@@ -1,101 +1,1457 @@
 use anchor_lang::prelude::*;
-use anchor_spl::token::{self, Mint, MintTo, Burn, TokenAccount, Token};
+use anchor_lang::Discriminator;
+use anchor_spl::token_interface::{self, Mint, MintTo, Burn, SetAuthority, FreezeAccount, ThawAccount, TokenAccount, TokenInterface};
+use anchor_spl::token_interface::spl_token_2022::{self, instruction::AuthorityType};
+use anchor_spl::associated_token::{self, AssociatedToken, Create};
+use anchor_lang::solana_program::program::invoke_signed;
+use spl_token_metadata_interface::state::{Field, TokenMetadata};
+use members::Members;
+use versioning::Upgrade;
 
-declare_id!("Fg6PaFpoGXkYsidMpWTK6W2BeZ7FEfcYkg476zPFsLnS");
+declare_id!("8VADzpWt5x3R4tZHrwqXPNQFey4praK48EzFh6hc2MUd");
+
+/// Number of reserve attestations kept in the ring buffer
+pub const RESERVE_HISTORY: usize = 16;
+
+/// Reserves are attested in satoshis, so the mint must use BTC's 8 decimals
+pub const BTC_DECIMALS: u8 = 8;
+
+/// Default delay before a queued config change can be executed
+pub const DEFAULT_TIMELOCK_DELAY: i64 = 2 * 86400;
+
+/// Controller layout version, see `members::CURRENT_VERSION` for the policy
+pub const CURRENT_VERSION: u8 = 4;
+
+/// Size unversioned Controller accounts were allocated with
+const LEGACY_CONTROLLER_SPACE: usize = 1000;
+
+const _: () = assert!(8 + Controller::INIT_SPACE != LEGACY_CONTROLLER_SPACE);
+
+/// The only program whose factory authority can be wired into a controller
+pub mod factory_program {
+    use anchor_lang::declare_id;
+
+    declare_id!("23ZpMy4UBi28oSN8XSkpVUr45GWPL8jXXzGXJjARhswL");
+}
+
+/// The factory crate depends on this one, so FactoryState is read by layout.
+/// The factory's tests pin these to its real Borsh layout
+pub const FACTORY_STATE_DISCRIMINATOR: [u8; 8] = [91, 157, 184, 99, 123, 112, 102, 7];
+
+/// Byte range of FactoryState.controller_state: discriminator, version, admin, controller_state
+pub const FACTORY_STATE_CONTROLLER_RANGE: std::ops::Range<usize> = 41..73;
+
+fn check_members(members: &AccountInfo) -> Result<()> {
+    if members.owner != &members::ID {
+        return Err(Errors::InvalidMembers.into())
+    }
+
+    Account::<Members>::try_from(members).map_err(|_| Errors::InvalidMembers)?;
+
+    Ok(())
+}
+
+fn check_factory(
+    factory: Pubkey,
+    factory_state: &AccountInfo,
+    factory_program: &AccountInfo,
+    controller_state: Pubkey
+) -> Result<()> {
+    if factory_program.key != &factory_program::ID || !factory_program.executable || factory_state.owner != factory_program.key {
+        return Err(Errors::InvalidFactory.into())
+    }
+
+    let data = factory_state.try_borrow_data()?;
+    if data.len() < FACTORY_STATE_CONTROLLER_RANGE.end || data[..8] != FACTORY_STATE_DISCRIMINATOR {
+        return Err(Errors::InvalidFactory.into())
+    }
+
+    if data[FACTORY_STATE_CONTROLLER_RANGE] != controller_state.to_bytes() {
+        return Err(Errors::FactoryControllerMismatch.into())
+    }
+
+    let (authority, _) = Pubkey::find_program_address(&[
+        b"factory_authority".as_ref(),
+        factory_state.key.as_ref()
+    ], factory_program.key);
+
+    if authority != factory {
+        return Err(Errors::InvalidFactory.into())
+    }
+
+    Ok(())
+}
+
+fn check_token_account(
+    token_account: &AccountInfo,
+    token_mint: Pubkey,
+    owner: Option<Pubkey>
+) -> Result<()> {
+    let token_account = InterfaceAccount::<TokenAccount>::try_from(token_account)
+        .map_err(|_| Errors::InvalidTokenAccount)?;
+
+    if token_account.mint != token_mint {
+        return Err(Errors::InvalidTokenAccount.into())
+    }
+
+    if owner.is_some_and(|owner| owner != token_account.owner) {
+        return Err(Errors::InvalidTokenAccount.into())
+    }
+
+    Ok(())
+}
 
 #[program]
 pub mod controller {
     use super::*;
 
-    pub fn initialize(ctx: Context<Initialize>) -> Result<()> {
-        let controller = &mut ctx.accounts.controller_state;
+    pub fn initialize(ctx: Context<Initialize>) -> Result<()> {
+        let controller = &mut ctx.accounts.controller_state;
+
+        controller.version = CURRENT_VERSION;
+        controller.token_mint = ctx.accounts.token_mint.key();
+        controller.owner = ctx.accounts.signer.key();
+        controller.bump = *ctx.bumps.get("controller_state").unwrap();
+        controller.mint_authority_bump = *ctx.bumps.get("mint_authority").unwrap();
+        controller.epoch_duration = 86400;
+        controller.timelock_delay = DEFAULT_TIMELOCK_DELAY;
+        controller.freeze_authority_bump = *ctx.bumps.get("freeze_authority").unwrap();
+
+        let cpi_accounts = SetAuthority {
+            current_authority: ctx.accounts.signer.to_account_info(),
+            account_or_mint: ctx.accounts.token_mint.to_account_info(),
+        };
+
+        let cpi_ctx = CpiContext::new(
+            ctx.accounts.token_program.to_account_info(),
+            cpi_accounts,
+        );
+
+        token_interface::set_authority(cpi_ctx, AuthorityType::MintTokens, Some(ctx.accounts.mint_authority.key()))?;
+
+        // Hand over the freeze authority too if the signer holds it
+        if ctx.accounts.token_mint.freeze_authority == Some(ctx.accounts.signer.key()).into() {
+            let cpi_accounts = SetAuthority {
+                current_authority: ctx.accounts.signer.to_account_info(),
+                account_or_mint: ctx.accounts.token_mint.to_account_info(),
+            };
+
+            let cpi_ctx = CpiContext::new(
+                ctx.accounts.token_program.to_account_info(),
+                cpi_accounts,
+            );
+
+            token_interface::set_authority(cpi_ctx, AuthorityType::FreezeAccount, Some(ctx.accounts.freeze_authority.key()))?;
+        }
+
+        emit!(ControllerInitialized {
+            controller: ctx.accounts.controller_state.key(),
+            token_mint: ctx.accounts.token_mint.key(),
+            owner: ctx.accounts.signer.key(),
+        });
+
+        Ok(())
+    }
+
+    pub fn transfer_ownership(ctx: Context<TransferOwnership>) -> Result<()> {
+        let controller = &mut ctx.accounts.controller_state;
+        controller.pending_owner = ctx.accounts.new_owner.key();
+
+        emit!(OwnershipTransferStarted {
+            controller: controller.key(),
+            owner: controller.owner,
+            pending_owner: controller.pending_owner,
+        });
+
+        Ok(())
+    }
+
+    pub fn accept_ownership(ctx: Context<AcceptOwnership>) -> Result<()> {
+        let controller = &mut ctx.accounts.controller_state;
+        let previous_owner = controller.owner;
+        controller.owner = controller.pending_owner;
+        controller.pending_owner = Pubkey::default();
+
+        emit!(OwnershipTransferred {
+            controller: controller.key(),
+            previous_owner,
+            new_owner: controller.owner,
+        });
+
+        Ok(())
+    }
+
+    pub fn renounce_ownership(ctx: Context<RenounceOwnership>) -> Result<()> {
+        let controller = &mut ctx.accounts.controller_state;
+        let previous_owner = controller.owner;
+        controller.owner = Pubkey::default();
+        controller.pending_owner = Pubkey::default();
+
+        emit!(OwnershipTransferred {
+            controller: controller.key(),
+            previous_owner,
+            new_owner: Pubkey::default(),
+        });
+
+        Ok(())
+    }
+
+    pub fn set_members(ctx: Context<SetMembers>) -> Result<()> {
+        check_members(&ctx.accounts.members)?;
+
+        let controller = &mut ctx.accounts.controller_state;
+        controller.members = ctx.accounts.members.key();
+
+        emit!(MembersSet {
+            controller: controller.key(),
+            members: controller.members,
+        });
+
+        Ok(())
+    }
+
+    pub fn set_factory(ctx: Context<SetFactory>) -> Result<()> {
+        check_factory(
+            ctx.accounts.factory.key(),
+            &ctx.accounts.factory_state,
+            &ctx.accounts.factory_program,
+            ctx.accounts.controller_state.key()
+        )?;
+
+        let controller = &mut ctx.accounts.controller_state;
+        controller.factory = ctx.accounts.factory.key();
+
+        emit!(FactorySet {
+            controller: controller.key(),
+            factory: controller.factory,
+        });
+
+        Ok(())
+    }
+
+    pub fn queue_config_change(ctx: Context<QueueConfigChange>, change: ConfigChange) -> Result<()> {
+        match change {
+            ConfigChange::Members(members) => {
+                let target = ctx.accounts.target.as_ref().ok_or(Errors::InvalidMembers)?;
+                if target.key() != members {
+                    return Err(Errors::InvalidMembers.into())
+                }
+
+                check_members(target)?;
+            }
+            ConfigChange::Factory(factory) => {
+                let factory_state = ctx.accounts.factory_state.as_ref().ok_or(Errors::InvalidFactory)?;
+                let factory_program = ctx.accounts.factory_program.as_ref().ok_or(Errors::InvalidFactory)?;
+
+                check_factory(factory, factory_state, factory_program, ctx.accounts.controller_state.key())?;
+            }
+            ConfigChange::TimelockDelay(delay) => {
+                if delay < 0 {
+                    return Err(Errors::InvalidTimelockDelay.into())
+                }
+            }
+        }
+
+        let controller = &ctx.accounts.controller_state;
+        let eta = Clock::get()?.unix_timestamp.checked_add(controller.timelock_delay).ok_or(Errors::InvalidTimelockDelay)?;
+
+        let pending_change = &mut ctx.accounts.pending_change;
+        pending_change.controller = controller.key();
+        pending_change.change = change.clone();
+        pending_change.proposer = ctx.accounts.signer.key();
+        pending_change.eta = eta;
+        pending_change.bump = *ctx.bumps.get("pending_change").unwrap();
+
+        emit!(ConfigChangeQueued {
+            controller: controller.key(),
+            change,
+            eta,
+        });
+
+        Ok(())
+    }
+
+    pub fn cancel_config_change(ctx: Context<CancelConfigChange>) -> Result<()> {
+        emit!(ConfigChangeCancelled {
+            controller: ctx.accounts.controller_state.key(),
+            change: ctx.accounts.pending_change.change.clone(),
+        });
+
+        Ok(())
+    }
+
+    pub fn execute_config_change(ctx: Context<ExecuteConfigChange>) -> Result<()> {
+        if Clock::get()?.unix_timestamp < ctx.accounts.pending_change.eta {
+            return Err(Errors::TimelockNotExpired.into())
+        }
+
+        let change = ctx.accounts.pending_change.change.clone();
+        let controller = &mut ctx.accounts.controller_state;
+
+        match change {
+            ConfigChange::Members(members) => {
+                controller.members = members;
+
+                emit!(MembersSet {
+                    controller: controller.key(),
+                    members,
+                });
+            }
+            ConfigChange::Factory(factory) => {
+                controller.factory = factory;
+
+                emit!(FactorySet {
+                    controller: controller.key(),
+                    factory,
+                });
+            }
+            ConfigChange::TimelockDelay(delay) => {
+                controller.timelock_delay = delay;
+            }
+        }
+
+        emit!(ConfigChangeExecuted {
+            controller: controller.key(),
+            change,
+        });
+
+        Ok(())
+    }
+
+    pub fn set_guardian(ctx: Context<SetGuardian>) -> Result<()> {
+        let controller = &mut ctx.accounts.controller_state;
+        controller.guardian = ctx.accounts.guardian.key();
+
+        emit!(GuardianSet {
+            controller: controller.key(),
+            guardian: controller.guardian,
+        });
+
+        Ok(())
+    }
+
+    pub fn set_pauser(ctx: Context<SetPauser>) -> Result<()> {
+        let controller = &mut ctx.accounts.controller_state;
+        controller.pauser = ctx.accounts.pauser.key();
+
+        emit!(PauserSet {
+            controller: controller.key(),
+            pauser: controller.pauser,
+        });
+
+        Ok(())
+    }
+
+    pub fn pause(ctx: Context<Pause>) -> Result<()> {
+        let controller = &mut ctx.accounts.controller_state;
+        controller.paused = true;
+
+        emit!(Paused {
+            controller: controller.key(),
+            by: ctx.accounts.signer.key(),
+        });
+
+        Ok(())
+    }
+
+    pub fn unpause(ctx: Context<Unpause>) -> Result<()> {
+        let controller = &mut ctx.accounts.controller_state;
+        controller.paused = false;
+
+        emit!(Unpaused {
+            controller: controller.key(),
+            by: ctx.accounts.signer.key(),
+        });
+
+        Ok(())
+    }
+
+    pub fn set_supply_caps(ctx: Context<SetSupplyCaps>, max_supply: u64, epoch_mint_limit: u64, epoch_duration: i64) -> Result<()> {
+        if epoch_duration <= 0 {
+            return Err(Errors::InvalidEpochDuration.into())
+        }
+
+        let controller = &mut ctx.accounts.controller_state;
+        controller.max_supply = max_supply;
+        controller.epoch_mint_limit = epoch_mint_limit;
+        controller.epoch_duration = epoch_duration;
+
+        emit!(SupplyCapsSet {
+            controller: controller.key(),
+            max_supply,
+            epoch_mint_limit,
+            epoch_duration,
+        });
+
+        Ok(())
+    }
+
+    pub fn set_compliance(ctx: Context<SetCompliance>) -> Result<()> {
+        let controller = &mut ctx.accounts.controller_state;
+        controller.compliance = ctx.accounts.compliance.key();
+
+        emit!(ComplianceSet {
+            controller: controller.key(),
+            compliance: controller.compliance,
+        });
+
+        Ok(())
+    }
+
+    pub fn freeze_account(ctx: Context<FreezeTokenAccount>, reason_code: u16) -> Result<()> {
+        let controller_key = ctx.accounts.controller_state.key();
+        let seeds = &[
+            b"freeze_authority".as_ref(),
+            controller_key.as_ref(),
+            &[ctx.accounts.controller_state.freeze_authority_bump],
+        ];
+        let signer = &[&seeds[..]];
+
+        let cpi_accounts = FreezeAccount {
+            account: ctx.accounts.token_account.to_account_info(),
+            mint: ctx.accounts.token_mint.to_account_info(),
+            authority: ctx.accounts.freeze_authority.to_account_info(),
+        };
+
+        let cpi_ctx = CpiContext::new_with_signer(
+            ctx.accounts.token_program.to_account_info(),
+            cpi_accounts,
+            signer,
+        );
+
+        token_interface::freeze_account(cpi_ctx)?;
+
+        let record = &mut ctx.accounts.freeze_record;
+        record.controller = controller_key;
+        record.token_account = ctx.accounts.token_account.key();
+        record.holder = ctx.accounts.token_account.owner;
+        record.reason_code = reason_code;
+        record.frozen_at = Clock::get()?.unix_timestamp;
+        record.frozen_by = ctx.accounts.signer.key();
+        record.bump = *ctx.bumps.get("freeze_record").unwrap();
+
+        emit!(AccountFrozen {
+            controller: controller_key,
+            token_account: record.token_account,
+            holder: record.holder,
+            reason_code,
+            by: record.frozen_by,
+        });
+
+        Ok(())
+    }
+
+    pub fn thaw_account(ctx: Context<ThawTokenAccount>) -> Result<()> {
+        let controller_key = ctx.accounts.controller_state.key();
+        let seeds = &[
+            b"freeze_authority".as_ref(),
+            controller_key.as_ref(),
+            &[ctx.accounts.controller_state.freeze_authority_bump],
+        ];
+        let signer = &[&seeds[..]];
+
+        let cpi_accounts = ThawAccount {
+            account: ctx.accounts.token_account.to_account_info(),
+            mint: ctx.accounts.token_mint.to_account_info(),
+            authority: ctx.accounts.freeze_authority.to_account_info(),
+        };
+
+        let cpi_ctx = CpiContext::new_with_signer(
+            ctx.accounts.token_program.to_account_info(),
+            cpi_accounts,
+            signer,
+        );
+
+        token_interface::thaw_account(cpi_ctx)?;
+
+        emit!(AccountThawed {
+            controller: controller_key,
+            token_account: ctx.accounts.token_account.key(),
+            holder: ctx.accounts.token_account.owner,
+            by: ctx.accounts.signer.key(),
+        });
+
+        Ok(())
+    }
+
+    /// Returns tokens sent to a controller PDA by mistake. Only token accounts owned
+    /// by the controller state, mint authority or freeze authority PDA can be drained,
+    /// never accounts those PDAs are merely delegates of. A transfer-hook mint's extra
+    /// accounts go in remaining accounts.
+    pub fn recover_tokens<'info>(ctx: Context<'_, '_, '_, 'info, RecoverTokens<'info>>, amount: u64) -> Result<()> {
+        let controller = &ctx.accounts.controller_state;
+        let controller_key = controller.key();
+        let authority = ctx.accounts.source.owner;
+
+        let controller_seeds: [&[u8]; 3] = [b"controller".as_ref(), controller.token_mint.as_ref(), &[controller.bump]];
+        let mint_authority_seeds: [&[u8]; 3] = [b"mint_authority".as_ref(), controller_key.as_ref(), &[controller.mint_authority_bump]];
+        let freeze_authority_seeds: [&[u8]; 3] = [b"freeze_authority".as_ref(), controller_key.as_ref(), &[controller.freeze_authority_bump]];
+
+        let seeds = [controller_seeds, mint_authority_seeds, freeze_authority_seeds]
+            .into_iter()
+            .find(|seeds| Pubkey::create_program_address(seeds, &crate::ID) == Ok(authority))
+            .ok_or(Errors::InvalidTokenAccount)?;
+        let signer = &[&seeds[..]];
+
+        // anchor-spl's transfer_checked drops remaining accounts, so build the instruction here
+        let mut ix = spl_token_2022::instruction::transfer_checked(
+            ctx.accounts.token_program.key,
+            &ctx.accounts.source.key(),
+            &ctx.accounts.mint.key(),
+            &ctx.accounts.destination.key(),
+            &authority,
+            &[],
+            amount,
+            ctx.accounts.mint.decimals,
+        )?;
+
+        let mut account_infos = vec![
+            ctx.accounts.source.to_account_info(),
+            ctx.accounts.mint.to_account_info(),
+            ctx.accounts.destination.to_account_info(),
+            ctx.accounts.authority.to_account_info(),
+        ];
+
+        for account in ctx.remaining_accounts {
+            ix.accounts.push(if account.is_writable {
+                AccountMeta::new(*account.key, account.is_signer)
+            } else {
+                AccountMeta::new_readonly(*account.key, account.is_signer)
+            });
+            account_infos.push(account.clone());
+        }
+
+        invoke_signed(&ix, &account_infos, signer)?;
+
+        emit!(TokensRecovered {
+            controller: controller_key,
+            mint: ctx.accounts.mint.key(),
+            source: ctx.accounts.source.key(),
+            destination: ctx.accounts.destination.key(),
+            amount,
+        });
+
+        Ok(())
+    }
+
+    pub fn set_metadata(ctx: Context<SetMetadata>, name: String, symbol: String, uri: String) -> Result<()> {
+        let metadata = TokenMetadata {
+            name: name.clone(),
+            symbol: symbol.clone(),
+            uri: uri.clone(),
+            ..Default::default()
+        };
+        let extra_len = metadata.tlv_size_of()?;
+        // Token-2022 reallocs the mint but doesn't pay for it
+        let token_mint = ctx.accounts.token_mint.to_account_info();
+        versioning::top_up_rent(&token_mint, &ctx.accounts.signer, &ctx.accounts.system_program, token_mint.data_len() + extra_len)?;
+
+        let controller_key = ctx.accounts.controller_state.key();
+        let seeds = &[
+            b"mint_authority".as_ref(),
+            controller_key.as_ref(),
+            &[ctx.accounts.controller_state.mint_authority_bump],
+        ];
+        let signer = &[&seeds[..]];
+
+        // The mint holds its own metadata, with the mint authority PDA as update authority
+        let ix = spl_token_metadata_interface::instruction::initialize(
+            &ctx.accounts.token_program.key(),
+            &ctx.accounts.token_mint.key(),
+            &ctx.accounts.mint_authority.key(),
+            &ctx.accounts.token_mint.key(),
+            &ctx.accounts.mint_authority.key(),
+            name,
+            symbol,
+            uri,
+        );
+
+        invoke_signed(
+            &ix,
+            &[
+                ctx.accounts.token_mint.to_account_info(),
+                ctx.accounts.mint_authority.to_account_info(),
+                ctx.accounts.token_program.to_account_info(),
+            ],
+            signer,
+        )?;
+
+        emit!(MetadataSet {
+            controller: controller_key,
+            token_mint: ctx.accounts.token_mint.key(),
+            name: metadata.name,
+            symbol: metadata.symbol,
+            uri: metadata.uri,
+        });
+
+        Ok(())
+    }
+
+    pub fn update_metadata(ctx: Context<UpdateMetadata>, field: MetadataField, value: String) -> Result<()> {
+        // Upper bound on growth: the new value, plus a key and both length prefixes for a new key
+        let extra_len = match &field {
+            MetadataField::Key(key) => key.len() + value.len() + 8,
+            _ => value.len(),
+        };
+        // Token-2022 reallocs the mint but doesn't pay for it
+        let token_mint = ctx.accounts.token_mint.to_account_info();
+        versioning::top_up_rent(&token_mint, &ctx.accounts.signer, &ctx.accounts.system_program, token_mint.data_len() + extra_len)?;
+
+        let controller_key = ctx.accounts.controller_state.key();
+        let seeds = &[
+            b"mint_authority".as_ref(),
+            controller_key.as_ref(),
+            &[ctx.accounts.controller_state.mint_authority_bump],
+        ];
+        let signer = &[&seeds[..]];
+
+        let ix = spl_token_metadata_interface::instruction::update_field(
+            &ctx.accounts.token_program.key(),
+            &ctx.accounts.token_mint.key(),
+            &ctx.accounts.mint_authority.key(),
+            field.clone().into(),
+            value.clone(),
+        );
+
+        invoke_signed(
+            &ix,
+            &[
+                ctx.accounts.token_mint.to_account_info(),
+                ctx.accounts.mint_authority.to_account_info(),
+                ctx.accounts.token_program.to_account_info(),
+            ],
+            signer,
+        )?;
+
+        emit!(MetadataUpdated {
+            controller: controller_key,
+            token_mint: ctx.accounts.token_mint.key(),
+            field,
+            value,
+        });
+
+        Ok(())
+    }
+
+    pub fn initialize_reserves(ctx: Context<InitializeReserves>) -> Result<()> {
+        let reserves = &mut ctx.accounts.reserves;
+        reserves.controller = ctx.accounts.controller_state.key();
+        reserves.bump = *ctx.bumps.get("reserves").unwrap();
+
+        Ok(())
+    }
+
+    pub fn set_reserve_check(ctx: Context<SetReserveCheck>, enabled: bool) -> Result<()> {
+        let controller = &mut ctx.accounts.controller_state;
+        controller.enforce_reserves = enabled;
+
+        emit!(ReserveCheckSet {
+            controller: controller.key(),
+            enabled,
+        });
+
+        Ok(())
+    }
+
+    pub fn attest_reserves(ctx: Context<AttestReserves>, btc_balance: u64, block_height: u64, report_hash: [u8; 32]) -> Result<()> {
+        let timestamp = Clock::get()?.unix_timestamp;
+
+        let reserves = &mut ctx.accounts.reserves;
+        let attestation = ReserveAttestation {
+            btc_balance,
+            block_height,
+            timestamp,
+            report_hash,
+            custodian: ctx.accounts.custodian.key(),
+        };
+        let head = reserves.head as usize;
+        reserves.attestations[head] = attestation;
+        reserves.head = ((head + 1) % RESERVE_HISTORY) as u8;
+        reserves.count += 1;
+
+        emit!(ReservesAttested {
+            controller: reserves.controller,
+            custodian: attestation.custodian,
+            btc_balance,
+            block_height,
+            timestamp,
+            report_hash,
+        });
+
+        Ok(())
+    }
+
+    pub fn mint(ctx: Context<MintCtx>, amount: u64) -> Result<()> {
+        let supply = ctx.accounts.token_mint.supply;
+        let now = Clock::get()?.unix_timestamp;
+
+        {
+            let controller = &mut ctx.accounts.controller_state;
+
+            // 0 means uncapped
+            if controller.max_supply != 0 && supply.checked_add(amount).ok_or(Errors::SupplyCapExceeded)? > controller.max_supply {
+                return Err(Errors::SupplyCapExceeded.into())
+            }
+
+            // Two buckets: the previous epoch's mints still count, weighted by how much
+            // of it overlaps the trailing epoch_duration window
+            let duration = controller.epoch_duration;
+            if now >= controller.epoch_start.saturating_add(duration.saturating_mul(2)) {
+                controller.prev_epoch_minted = 0;
+                controller.epoch_start = now;
+                controller.epoch_minted = 0;
+            } else if now >= controller.epoch_start.saturating_add(duration) {
+                controller.prev_epoch_minted = controller.epoch_minted;
+                controller.epoch_start += duration;
+                controller.epoch_minted = 0;
+            }
+
+            let epoch_minted = controller.epoch_minted.checked_add(amount).ok_or(Errors::RateLimitExceeded)?;
+            if controller.epoch_mint_limit != 0 {
+                let remaining = (duration - (now - controller.epoch_start)) as u128;
+                let carried = controller.prev_epoch_minted as u128 * remaining / duration as u128;
+
+                if carried + epoch_minted as u128 > controller.epoch_mint_limit as u128 {
+                    return Err(Errors::RateLimitExceeded.into())
+                }
+            }
+            controller.epoch_minted = epoch_minted;
+
+            // Supply is backed 1:1 by attested satoshis
+            if controller.enforce_reserves {
+                let reserves = ctx.accounts.reserves.as_ref().ok_or(Errors::ReservesRequired)?;
+                let latest = reserves.latest().ok_or(Errors::ReservesExceeded)?;
+
+                if ctx.accounts.token_mint.decimals != BTC_DECIMALS {
+                    return Err(Errors::ReservesDecimalsMismatch.into())
+                }
+
+                if supply.checked_add(amount).ok_or(Errors::ReservesExceeded)? > latest.btc_balance {
+                    return Err(Errors::ReservesExceeded.into())
+                }
+            }
+        }
+
+        // Create the recipient's associated token account when it doesn't exist yet
+        if ctx.accounts.to_token_account.data_is_empty() {
+            let (Some(recipient), Some(payer), Some(associated_token_program), Some(system_program)) = (
+                ctx.accounts.recipient.as_ref(),
+                ctx.accounts.payer.as_ref(),
+                ctx.accounts.associated_token_program.as_ref(),
+                ctx.accounts.system_program.as_ref(),
+            ) else {
+                return Err(Errors::InvalidTokenAccount.into())
+            };
+
+            let cpi_accounts = Create {
+                payer: payer.to_account_info(),
+                associated_token: ctx.accounts.to_token_account.to_account_info(),
+                authority: recipient.to_account_info(),
+                mint: ctx.accounts.token_mint.to_account_info(),
+                system_program: system_program.to_account_info(),
+                token_program: ctx.accounts.token_program.to_account_info(),
+            };
+
+            associated_token::create(CpiContext::new(associated_token_program.to_account_info(), cpi_accounts))?;
+        }
+
+        check_token_account(
+            &ctx.accounts.to_token_account,
+            ctx.accounts.controller_state.token_mint,
+            ctx.accounts.recipient.as_ref().map(|recipient| recipient.key()),
+        )?;
+
+        let controller_key = ctx.accounts.controller_state.key();
+        let seeds = &[
+            b"mint_authority".as_ref(),
+            controller_key.as_ref(),
+            &[ctx.accounts.controller_state.mint_authority_bump],
+        ];
+        let signer = &[&seeds[..]];
+
+        let cpi_accounts = MintTo {
+            mint: ctx.accounts.token_mint.to_account_info(),
+            to: ctx.accounts.to_token_account.to_account_info(),
+            authority: ctx.accounts.mint_authority.to_account_info(),
+        };
+
+        let cpi_ctx = CpiContext::new_with_signer(
+            ctx.accounts.token_program.to_account_info(),
+            cpi_accounts,
+            signer,
+        );
+        
+        token_interface::mint_to(cpi_ctx, amount)?;
+
+        let controller = &mut ctx.accounts.controller_state;
+        controller.total_minted = controller.total_minted.checked_add(amount).ok_or(Errors::StatsOverflow)?;
+        controller.mint_count = controller.mint_count.checked_add(1).ok_or(Errors::StatsOverflow)?;
+
+        emit!(Minted {
+            controller: controller_key,
+            to: ctx.accounts.to_token_account.key(),
+            amount,
+        });
+
+        Ok(())
+    }
+
+    pub fn burn(ctx: Context<BurnCtx>, amount: u64) -> Result<()> {
+        let controller_key = ctx.accounts.controller_state.key();
+
+        // Tokens only ever leave an account with its owner's signature
+        let cpi_accounts = Burn {
+            mint: ctx.accounts.token_mint.to_account_info(),
+            from: ctx.accounts.from_token_account.to_account_info(),
+            authority: ctx.accounts.owner.to_account_info(),
+        };
+
+        let cpi_ctx = CpiContext::new(
+            ctx.accounts.token_program.to_account_info(),
+            cpi_accounts,
+        );
+        
+        token_interface::burn(cpi_ctx, amount)?;
+
+        let controller = &mut ctx.accounts.controller_state;
+        controller.total_burned = controller.total_burned.checked_add(amount).ok_or(Errors::StatsOverflow)?;
+        controller.burn_count = controller.burn_count.checked_add(1).ok_or(Errors::StatsOverflow)?;
+
+        emit!(Burned {
+            controller: controller_key,
+            from: ctx.accounts.from_token_account.key(),
+            amount,
+        });
+
+        Ok(())
+    }
+
+    pub fn get_stats(ctx: Context<GetStats>) -> Result<ControllerStats> {
+        let controller = &ctx.accounts.controller_state;
+
+        Ok(ControllerStats {
+            total_minted: controller.total_minted,
+            total_burned: controller.total_burned,
+            mint_count: controller.mint_count,
+            burn_count: controller.burn_count,
+        })
+    }
+
+    pub fn get_config(ctx: Context<GetConfig>) -> Result<ControllerConfig> {
+        let controller = &ctx.accounts.controller_state;
+
+        Ok(ControllerConfig {
+            owner: controller.owner,
+            pending_owner: controller.pending_owner,
+            token_mint: controller.token_mint,
+            members: controller.members,
+            factory: controller.factory,
+            paused: controller.paused,
+            pauser: controller.pauser,
+            guardian: controller.guardian,
+            compliance: controller.compliance,
+            max_supply: controller.max_supply,
+            epoch_mint_limit: controller.epoch_mint_limit,
+            epoch_duration: controller.epoch_duration,
+            enforce_reserves: controller.enforce_reserves,
+            timelock_delay: controller.timelock_delay,
+        })
+    }
+
+    pub fn migrate(ctx: Context<Migrate>) -> Result<()> {
+        let account = &ctx.accounts.account;
+        if account.owner != &crate::ID {
+            return Err(Errors::InvalidMigration.into())
+        }
+
+        let upgrade = {
+            let data = account.try_borrow_data()?;
+            if data.len() < 9 || data[..8] != Controller::DISCRIMINATOR {
+                return Err(Errors::InvalidMigration.into())
+            }
+
+            if data.len() == LEGACY_CONTROLLER_SPACE {
+                let legacy = ControllerV0::deserialize(&mut &data[8..]).map_err(|_| Errors::InvalidMigration)?;
+
+                Upgrade::rewrite(&legacy.upgrade(account.key()))?
+            } else {
+                Upgrade::Grow(data[8])
+            }
+        };
+
+        let from_version = upgrade.from_version();
+        if from_version >= CURRENT_VERSION {
+            return Err(Errors::AlreadyMigrated.into())
+        }
+
+        versioning::apply(account, &ctx.accounts.payer, &ctx.accounts.system_program, upgrade, 8 + Controller::INIT_SPACE, CURRENT_VERSION)?;
+
+        emit!(Migrated {
+            account: account.key(),
+            from_version,
+            to_version: CURRENT_VERSION,
+        });
+
+        Ok(())
+    }
+}
+
+#[derive(Accounts)]
+pub struct Initialize<'info> {
+    #[account(
+        init,
+        seeds = [
+            b"controller".as_ref(),
+            token_mint.key().as_ref()
+        ],
+        bump,
+        space = 8 + Controller::INIT_SPACE,
+        payer = signer
+    )]
+    pub controller_state: Account<'info, Controller>,
+
+    #[account(mut)]
+    pub token_mint: InterfaceAccount<'info, Mint>,
+
+    /// CHECK: PDA that becomes the mint authority of token_mint
+    #[account(
+        seeds = [
+            b"mint_authority".as_ref(),
+            controller_state.key().as_ref()
+        ],
+        bump,
+    )]
+    pub mint_authority: UncheckedAccount<'info>,
+
+    /// CHECK: PDA that takes over the freeze authority of token_mint
+    #[account(
+        seeds = [
+            b"freeze_authority".as_ref(),
+            controller_state.key().as_ref()
+        ],
+        bump,
+    )]
+    pub freeze_authority: UncheckedAccount<'info>,
+
+    /// Current mint authority of token_mint
+    #[account(mut)]
+    pub signer: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+    pub token_program: Interface<'info, TokenInterface>
+}
+
+#[derive(Accounts)]
+pub struct TransferOwnership<'info> {
+    #[account(
+        mut
+    )]
+    pub controller_state: Account<'info, Controller>,
+
+    /// CHECK: New owner, only recorded as pending until it signs accept_ownership
+    pub new_owner: UncheckedAccount<'info>,
+
+    #[account(
+        mut,
+        constraint = signer.key() == controller_state.owner
+    )]
+    pub signer: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct AcceptOwnership<'info> {
+    #[account(
+        mut,
+        constraint = signer.key() == controller_state.pending_owner @ Errors::NotPendingOwner
+    )]
+    pub controller_state: Account<'info, Controller>,
+
+    #[account(mut)]
+    pub signer: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct RenounceOwnership<'info> {
+    #[account(
+        mut
+    )]
+    pub controller_state: Account<'info, Controller>,
+
+    #[account(
+        mut,
+        constraint = signer.key() == controller_state.owner
+    )]
+    pub signer: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct SetMembers<'info> {
+    #[account(
+        mut,
+        constraint = controller_state.members == Pubkey::default() @ Errors::TimelockRequired
+    )]
+    pub controller_state: Account<'info, Controller>,
+
+    /// CHECK: Checked to be a Members account owned by the members program
+    pub members: UncheckedAccount<'info>,
+
+    #[account(
+        mut,
+        constraint = signer.key() == controller_state.owner
+    )]
+    pub signer: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct SetFactory<'info> {
+    #[account(
+        mut,
+        constraint = controller_state.factory == Pubkey::default() @ Errors::TimelockRequired
+    )]
+    pub controller_state: Account<'info, Controller>,
+
+    /// CHECK: Checked to be the authority PDA of factory_state
+    pub factory: UncheckedAccount<'info>,
+
+    /// CHECK: Checked to be a FactoryState pointing at controller_state
+    pub factory_state: UncheckedAccount<'info>,
+
+    /// CHECK: Checked to be the executable owner of factory_state
+    pub factory_program: UncheckedAccount<'info>,
+
+    #[account(
+        mut,
+        constraint = signer.key() == controller_state.owner
+    )]
+    pub signer: Signer<'info>,
+}
+
+#[derive(Accounts)]
+#[instruction(change: ConfigChange)]
+pub struct QueueConfigChange<'info> {
+    pub controller_state: Account<'info, Controller>,
+
+    #[account(
+        init,
+        seeds = [
+            b"pending_change".as_ref(),
+            controller_state.key().as_ref(),
+            &[change.kind()]
+        ],
+        bump,
+        payer = signer,
+        space = 8 + PendingChange::INIT_SPACE
+    )]
+    pub pending_change: Account<'info, PendingChange>,
+
+    /// CHECK: New members state, required for a members change
+    pub target: Option<UncheckedAccount<'info>>,
+
+    /// CHECK: Factory state behind a factory change
+    pub factory_state: Option<UncheckedAccount<'info>>,
+
+    /// CHECK: Program owning factory_state, required for a factory change
+    pub factory_program: Option<UncheckedAccount<'info>>,
+
+    #[account(
+        mut,
+        constraint = signer.key() == controller_state.owner
+    )]
+    pub signer: Signer<'info>,
+
+    pub system_program: Program<'info, System>
+}
+
+#[derive(Accounts)]
+pub struct CancelConfigChange<'info> {
+    pub controller_state: Account<'info, Controller>,
+
+    #[account(
+        mut,
+        close = proposer,
+        has_one = proposer,
+        seeds = [
+            b"pending_change".as_ref(),
+            controller_state.key().as_ref(),
+            &[pending_change.change.kind()]
+        ],
+        bump = pending_change.bump,
+    )]
+    pub pending_change: Account<'info, PendingChange>,
+
+    /// CHECK: Receives the pending change's rent back
+    #[account(mut)]
+    pub proposer: UncheckedAccount<'info>,
+
+    #[account(
+        mut,
+        constraint = signer.key() == controller_state.owner
+    )]
+    pub signer: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct ExecuteConfigChange<'info> {
+    #[account(
+        mut
+    )]
+    pub controller_state: Account<'info, Controller>,
+
+    #[account(
+        mut,
+        close = proposer,
+        has_one = proposer,
+        seeds = [
+            b"pending_change".as_ref(),
+            controller_state.key().as_ref(),
+            &[pending_change.change.kind()]
+        ],
+        bump = pending_change.bump,
+    )]
+    pub pending_change: Account<'info, PendingChange>,
+
+    /// CHECK: Receives the pending change's rent back
+    #[account(mut)]
+    pub proposer: UncheckedAccount<'info>,
+
+    /// Anyone may execute once the delay has passed
+    pub signer: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct SetSupplyCaps<'info> {
+    #[account(
+        mut
+    )]
+    pub controller_state: Account<'info, Controller>,
+
+    #[account(
+        mut,
+        constraint = signer.key() == controller_state.owner
+    )]
+    pub signer: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct SetGuardian<'info> {
+    #[account(
+        mut
+    )]
+    pub controller_state: Account<'info, Controller>,
+
+    /// CHECK: Any key, default pubkey disables the guardian
+    pub guardian: UncheckedAccount<'info>,
+
+    #[account(
+        mut,
+        constraint = signer.key() == controller_state.owner
+    )]
+    pub signer: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct SetPauser<'info> {
+    #[account(
+        mut
+    )]
+    pub controller_state: Account<'info, Controller>,
+
+    /// CHECK: Any key, default pubkey disables the pauser
+    pub pauser: UncheckedAccount<'info>,
+
+    #[account(
+        mut,
+        constraint = signer.key() == controller_state.owner
+    )]
+    pub signer: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct Pause<'info> {
+    #[account(
+        mut,
+        constraint = signer.key() == controller_state.owner
+            || signer.key() == controller_state.pauser
+            || signer.key() == controller_state.guardian @ Errors::SenderNotAuthorized
+    )]
+    pub controller_state: Account<'info, Controller>,
+
+    pub signer: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct Unpause<'info> {
+    #[account(
+        mut,
+        constraint = signer.key() == controller_state.owner || signer.key() == controller_state.pauser @ Errors::SenderNotAuthorized
+    )]
+    pub controller_state: Account<'info, Controller>,
+
+    pub signer: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct SetMetadata<'info> {
+    pub controller_state: Account<'info, Controller>,
+
+    #[account(
+        mut,
+        constraint = token_mint.key() == controller_state.token_mint
+    )]
+    pub token_mint: InterfaceAccount<'info, Mint>,
+
+    /// CHECK: PDA mint authority, signed for by the controller
+    #[account(
+        seeds = [
+            b"mint_authority".as_ref(),
+            controller_state.key().as_ref()
+        ],
+        bump = controller_state.mint_authority_bump,
+    )]
+    pub mint_authority: UncheckedAccount<'info>,
+
+    /// Owner, pays for the mint's extra rent
+    #[account(
+        mut,
+        constraint = signer.key() == controller_state.owner
+    )]
+    pub signer: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+    #[account(
+        constraint = token_program.key() == spl_token_2022::ID @ Errors::MetadataRequiresToken2022
+    )]
+    pub token_program: Interface<'info, TokenInterface>
+}
+
+#[derive(Accounts)]
+pub struct UpdateMetadata<'info> {
+    pub controller_state: Account<'info, Controller>,
+
+    #[account(
+        mut,
+        constraint = token_mint.key() == controller_state.token_mint
+    )]
+    pub token_mint: InterfaceAccount<'info, Mint>,
+
+    /// CHECK: PDA mint authority, signed for by the controller
+    #[account(
+        seeds = [
+            b"mint_authority".as_ref(),
+            controller_state.key().as_ref()
+        ],
+        bump = controller_state.mint_authority_bump,
+    )]
+    pub mint_authority: UncheckedAccount<'info>,
+
+    /// Owner, pays for the mint's extra rent
+    #[account(
+        mut,
+        constraint = signer.key() == controller_state.owner
+    )]
+    pub signer: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+    #[account(
+        constraint = token_program.key() == spl_token_2022::ID @ Errors::MetadataRequiresToken2022
+    )]
+    pub token_program: Interface<'info, TokenInterface>
+}
+
+#[derive(Accounts)]
+pub struct SetCompliance<'info> {
+    #[account(
+        mut
+    )]
+    pub controller_state: Account<'info, Controller>,
+
+    /// CHECK: Any key, default pubkey disables the compliance role
+    pub compliance: UncheckedAccount<'info>,
+
+    #[account(
+        mut,
+        constraint = signer.key() == controller_state.owner
+    )]
+    pub signer: Signer<'info>,
+}
 
-        controller.token_mint = ctx.accounts.token_mint.key();
-        controller.owner = ctx.accounts.signer.key();
+#[derive(Accounts)]
+pub struct FreezeTokenAccount<'info> {
+    #[account(
+        constraint = signer.key() == controller_state.owner || signer.key() == controller_state.compliance @ Errors::SenderNotAuthorized
+    )]
+    pub controller_state: Account<'info, Controller>,
 
-        Ok(())
-    }
+    #[account(
+        constraint = token_mint.key() == controller_state.token_mint
+    )]
+    pub token_mint: InterfaceAccount<'info, Mint>,
+    #[account(
+        mut,
+        constraint = token_account.mint == token_mint.key()
+    )]
+    pub token_account: InterfaceAccount<'info, TokenAccount>,
 
-    pub fn set_members(ctx: Context<SetMembers>) -> Result<()> {
-        let controller = &mut ctx.accounts.controller_state;
-        controller.members = ctx.accounts.members.key();
+    /// CHECK: PDA freeze authority, signed for by the controller
+    #[account(
+        seeds = [
+            b"freeze_authority".as_ref(),
+            controller_state.key().as_ref()
+        ],
+        bump = controller_state.freeze_authority_bump,
+    )]
+    pub freeze_authority: UncheckedAccount<'info>,
 
-        Ok(())
-    }
+    #[account(
+        init,
+        seeds = [
+            b"freeze_record".as_ref(),
+            controller_state.key().as_ref(),
+            token_account.key().as_ref()
+        ],
+        bump,
+        payer = signer,
+        space = 8 + FreezeRecord::INIT_SPACE
+    )]
+    pub freeze_record: Account<'info, FreezeRecord>,
 
-    pub fn set_factory(ctx: Context<SetFactory>) -> Result<()> {
-        let controller = &mut ctx.accounts.controller_state;
-        controller.factory = ctx.accounts.factory.key();
+    #[account(mut)]
+    pub signer: Signer<'info>,
 
-        Ok(())
-    }
+    pub system_program: Program<'info, System>,
+    pub token_program: Interface<'info, TokenInterface>
+}
 
-    pub fn mint(ctx: Context<MintCtx>, amount: u64) -> Result<()> {
-        let controller = &mut ctx.accounts.controller_state;
-        
-        let cpi_accounts = MintTo {
-            mint: ctx.accounts.token_mint.to_account_info(),
-            to: ctx.accounts.to_token_account.to_account_info(),
-            authority: ctx.accounts.signer.to_account_info(),
-        };
+#[derive(Accounts)]
+pub struct ThawTokenAccount<'info> {
+    #[account(
+        constraint = signer.key() == controller_state.owner || signer.key() == controller_state.compliance @ Errors::SenderNotAuthorized
+    )]
+    pub controller_state: Account<'info, Controller>,
 
-        let cpi_ctx = CpiContext::new(
-            ctx.accounts.token_program.to_account_info(),
-            cpi_accounts,
-        );
-        
-        token::mint_to(cpi_ctx, amount)?;
+    #[account(
+        constraint = token_mint.key() == controller_state.token_mint
+    )]
+    pub token_mint: InterfaceAccount<'info, Mint>,
+    #[account(
+        mut,
+        constraint = token_account.mint == token_mint.key()
+    )]
+    pub token_account: InterfaceAccount<'info, TokenAccount>,
 
-        Ok(())
-    }
+    /// CHECK: PDA freeze authority, signed for by the controller
+    #[account(
+        seeds = [
+            b"freeze_authority".as_ref(),
+            controller_state.key().as_ref()
+        ],
+        bump = controller_state.freeze_authority_bump,
+    )]
+    pub freeze_authority: UncheckedAccount<'info>,
 
-    pub fn burn(ctx: Context<BurnCtx>, amount: u64) -> Result<()> {
-        let controller = &mut ctx.accounts.controller_state;
-        
-        let cpi_accounts = Burn {
-            mint: ctx.accounts.token_mint.to_account_info(),
-            from: ctx.accounts.from_token_account.to_account_info(),
-            authority: ctx.accounts.signer.to_account_info(),
-        };
+    #[account(
+        mut,
+        close = signer,
+        seeds = [
+            b"freeze_record".as_ref(),
+            controller_state.key().as_ref(),
+            token_account.key().as_ref()
+        ],
+        bump = freeze_record.bump,
+    )]
+    pub freeze_record: Account<'info, FreezeRecord>,
 
-        let cpi_ctx = CpiContext::new(
-            ctx.accounts.token_program.to_account_info(),
-            cpi_accounts,
-        );
-        
-        token::burn(cpi_ctx, amount)?;
+    #[account(mut)]
+    pub signer: Signer<'info>,
 
-        Ok(())
-    }
+    pub token_program: Interface<'info, TokenInterface>
 }
 
 #[derive(Accounts)]
-pub struct Initialize<'info> {
+pub struct RecoverTokens<'info> {
     #[account(
-        init,
-        space = 1000,
-        payer = signer
+        has_one = owner
+    )]
+    pub controller_state: Account<'info, Controller>,
+
+    pub owner: Signer<'info>,
+
+    pub mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        mut,
+        constraint = source.mint == mint.key() @ Errors::InvalidTokenAccount,
+        constraint = source.owner == authority.key() @ Errors::InvalidTokenAccount
+    )]
+    pub source: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        constraint = destination.mint == mint.key() @ Errors::InvalidTokenAccount
     )]
+    pub destination: InterfaceAccount<'info, TokenAccount>,
+
+    /// CHECK: Controller PDA owning source, verified against its stored bump
+    pub authority: UncheckedAccount<'info>,
+
+    pub token_program: Interface<'info, TokenInterface>
+}
+
+#[derive(Accounts)]
+pub struct GetStats<'info> {
     pub controller_state: Account<'info, Controller>,
+}
+
+#[derive(Accounts)]
+pub struct GetConfig<'info> {
+    pub controller_state: Account<'info, Controller>,
+}
 
-    pub token_mint: Account<'info, Mint>,
+#[derive(Accounts)]
+pub struct Migrate<'info> {
+    /// CHECK: Owner and discriminator are checked in migrate
+    #[account(mut)]
+    pub account: UncheckedAccount<'info>,
 
+    /// Covers the rent of the larger layout, anyone may migrate
     #[account(mut)]
+    pub payer: Signer<'info>,
+
+    pub system_program: Program<'info, System>
+}
+
+#[derive(Accounts)]
+pub struct InitializeReserves<'info> {
+    pub controller_state: Account<'info, Controller>,
+
+    #[account(
+        init,
+        seeds = [
+            b"reserves".as_ref(),
+            controller_state.key().as_ref()
+        ],
+        bump,
+        payer = signer,
+        space = 8 + Reserves::INIT_SPACE
+    )]
+    pub reserves: Box<Account<'info, Reserves>>,
+
+    #[account(
+        mut,
+        constraint = signer.key() == controller_state.owner
+    )]
     pub signer: Signer<'info>,
 
     pub system_program: Program<'info, System>
 }
 
 #[derive(Accounts)]
-pub struct SetMembers<'info> {
+pub struct SetReserveCheck<'info> {
     #[account(
         mut
     )]
     pub controller_state: Account<'info, Controller>,
 
-    /// CHECK: Should be Members 
-    pub members: UncheckedAccount<'info>,
-
     #[account(
         mut,
         constraint = signer.key() == controller_state.owner
@@ -104,85 +1460,580 @@ pub struct SetMembers<'info> {
 }
 
 #[derive(Accounts)]
-pub struct SetFactory<'info> {
+pub struct AttestReserves<'info> {
     #[account(
-        mut
+        has_one = members
     )]
     pub controller_state: Account<'info, Controller>,
 
-    /// CHECK: Should be factory 
-    pub factory: UncheckedAccount<'info>,
+    #[account(
+        constraint = members.custodian == custodian.key() @ Errors::SenderNotAuthorized
+    )]
+    pub members: Account<'info, Members>,
 
     #[account(
         mut,
-        constraint = signer.key() == controller_state.owner
+        seeds = [
+            b"reserves".as_ref(),
+            controller_state.key().as_ref()
+        ],
+        bump = reserves.bump,
     )]
-    pub signer: Signer<'info>,
+    pub reserves: Box<Account<'info, Reserves>>,
+
+    pub custodian: Signer<'info>,
 }
 
 #[derive(Accounts)]
 pub struct MintCtx<'info> {
     #[account(
         mut,
-        constraint = signer.key() == controller_state.factory @ Errors::SenderNotAuthorized
+        constraint = signer.key() == controller_state.factory @ Errors::SenderNotAuthorized,
+        constraint = !controller_state.paused @ Errors::Paused
     )]
     pub controller_state: Account<'info, Controller>,
 
     #[account(
+        mut,
         constraint = token_mint.key() == controller_state.token_mint
     )]
-    pub token_mint: Account<'info, Mint>,
+    pub token_mint: InterfaceAccount<'info, Mint>,
+    /// CHECK: Mint (and owner, when recipient is given) verified by the controller,
+    /// created as the recipient's associated token account when empty
+    #[account(mut)]
+    pub to_token_account: UncheckedAccount<'info>,
+
+    /// CHECK: Expected owner of to_token_account, required to create it
+    pub recipient: Option<UncheckedAccount<'info>>,
+
+    /// Pays for the associated token account, only required to create it
     #[account(mut)]
-    pub to_token_account: Account<'info, TokenAccount>,
+    pub payer: Option<Signer<'info>>,
+
+    /// CHECK: PDA mint authority, signed for by the controller
+    #[account(
+        seeds = [
+            b"mint_authority".as_ref(),
+            controller_state.key().as_ref()
+        ],
+        bump = controller_state.mint_authority_bump,
+    )]
+    pub mint_authority: UncheckedAccount<'info>,
 
+    /// Factory authority PDA, signed for by the factory program
+    pub signer: Signer<'info>,
+
+    /// Only required while the reserve check is enabled
     #[account(
-        constraint = signer.is_signer == true
+        seeds = [
+            b"reserves".as_ref(),
+            controller_state.key().as_ref()
+        ],
+        bump = reserves.bump,
     )]
-    pub signer: AccountInfo<'info>,
+    pub reserves: Option<Box<Account<'info, Reserves>>>,
 
-    pub token_program: Program<'info, Token>
+    pub token_program: Interface<'info, TokenInterface>,
+    pub associated_token_program: Option<Program<'info, AssociatedToken>>,
+    pub system_program: Option<Program<'info, System>>,
 }
 
 #[derive(Accounts)]
 pub struct BurnCtx<'info> {
     #[account(
         mut,
-        constraint = signer.key() == controller_state.factory @ Errors::SenderNotAuthorized
+        constraint = signer.key() == controller_state.factory @ Errors::SenderNotAuthorized,
+        constraint = !controller_state.paused @ Errors::Paused
     )]
     pub controller_state: Account<'info, Controller>,
 
     #[account(
+        mut,
         constraint = token_mint.key() == controller_state.token_mint
     )]
-    pub token_mint: Account<'info, Mint>,
-    #[account(mut)]
-    pub from_token_account: Account<'info, TokenAccount>,
-
+    pub token_mint: InterfaceAccount<'info, Mint>,
     #[account(
         mut,
+        constraint = from_token_account.mint == token_mint.key() @ Errors::InvalidTokenAccount,
+        constraint = from_token_account.owner == owner.key() @ Errors::InvalidTokenAccount
     )]
+    pub from_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    /// Owner of from_token_account
+    pub owner: Signer<'info>,
+
+    /// Factory authority PDA, signed for by the factory program
     pub signer: Signer<'info>,
 
-    pub token_program: Program<'info, Token>
+    pub token_program: Interface<'info, TokenInterface>
+}
+
+/// Controller layout from before versioning
+#[derive(AnchorDeserialize)]
+struct ControllerV0 {
+    owner: Pubkey,
+    token_mint: Pubkey,
+    members: Pubkey,
+    factory: Pubkey,
+    paused: bool,
+}
+
+impl ControllerV0 {
+    /// Fills the fields added since with what initialize would have set
+    fn upgrade(self, controller: Pubkey) -> Controller {
+        let bump = |seeds: &[&[u8]]| Pubkey::find_program_address(seeds, &crate::ID).1;
+
+        Controller {
+            version: CURRENT_VERSION,
+            owner: self.owner,
+            pending_owner: Pubkey::default(),
+            token_mint: self.token_mint,
+            members: self.members,
+            factory: self.factory,
+            paused: self.paused,
+            pauser: Pubkey::default(),
+            mint_authority_bump: bump(&[b"mint_authority".as_ref(), controller.as_ref()]),
+            max_supply: 0,
+            epoch_mint_limit: 0,
+            epoch_duration: 86400,
+            epoch_start: 0,
+            epoch_minted: 0,
+            compliance: Pubkey::default(),
+            freeze_authority_bump: bump(&[b"freeze_authority".as_ref(), controller.as_ref()]),
+            enforce_reserves: false,
+            timelock_delay: DEFAULT_TIMELOCK_DELAY,
+            bump: bump(&[b"controller".as_ref(), self.token_mint.as_ref()]),
+            total_minted: 0,
+            total_burned: 0,
+            mint_count: 0,
+            burn_count: 0,
+            guardian: Pubkey::default(),
+            prev_epoch_minted: 0,
+        }
+    }
 }
 
 #[account]
+#[derive(InitSpace)]
 pub struct Controller {
+    /// Layout Version
+    pub version: u8,
     /// Owner
     pub owner: Pubkey,
+    /// Pending Owner - must accept before becoming owner
+    pub pending_owner: Pubkey,
     /// WBTC Mint
     pub token_mint: Pubkey,
     /// Members Program
     pub members: Pubkey,
-    /// Factory
+    /// Factory Authority PDA
     pub factory: Pubkey,
 
     /// Pause State
-    pub paused: bool
+    pub paused: bool,
+    /// Pauser - may pause/unpause alongside the owner
+    pub pauser: Pubkey,
+    /// Mint Authority PDA Bump
+    pub mint_authority_bump: u8,
+
+    /// Max Total Supply - 0 for uncapped
+    pub max_supply: u64,
+    /// Max Minted In Any Trailing Epoch - 0 for unlimited
+    pub epoch_mint_limit: u64,
+    /// Epoch Length In Seconds - also the length of the rolling window
+    pub epoch_duration: i64,
+    /// Current Bucket Start Timestamp
+    pub epoch_start: i64,
+    /// Minted In Current Bucket
+    pub epoch_minted: u64,
+
+    /// Compliance - may freeze/thaw holders alongside the owner
+    pub compliance: Pubkey,
+    /// Freeze Authority PDA Bump
+    pub freeze_authority_bump: u8,
+
+    /// Refuse mints beyond the latest reserve attestation
+    pub enforce_reserves: bool,
+
+    /// Delay In Seconds Before Queued Config Changes Apply
+    pub timelock_delay: i64,
+
+    /// Seed Bump
+    pub bump: u8,
+
+    /// Total Minted
+    pub total_minted: u64,
+    /// Total Burned
+    pub total_burned: u64,
+    /// Mint Count
+    pub mint_count: u64,
+    /// Burn Count
+    pub burn_count: u64,
+
+    /// Guardian - may pause, freeze merchants and block mint requests, but never unpause
+    pub guardian: Pubkey,
+
+    /// Minted In Previous Bucket
+    pub prev_epoch_minted: u64,
+}
+
+/// Return data of get_stats
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug)]
+pub struct ControllerStats {
+    pub total_minted: u64,
+    pub total_burned: u64,
+    pub mint_count: u64,
+    pub burn_count: u64,
+}
+
+/// Return data of get_config, fields are only ever appended
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug)]
+pub struct ControllerConfig {
+    pub owner: Pubkey,
+    pub pending_owner: Pubkey,
+    pub token_mint: Pubkey,
+    pub members: Pubkey,
+    pub factory: Pubkey,
+    pub paused: bool,
+    pub pauser: Pubkey,
+    pub guardian: Pubkey,
+    pub compliance: Pubkey,
+    pub max_supply: u64,
+    pub epoch_mint_limit: u64,
+    pub epoch_duration: i64,
+    pub enforce_reserves: bool,
+    pub timelock_delay: i64,
+}
+
+#[account]
+#[derive(InitSpace)]
+pub struct PendingChange {
+    /// Controller
+    pub controller: Pubkey,
+    /// Queued Change
+    pub change: ConfigChange,
+    /// Owner that queued it, refunded on execute/cancel
+    pub proposer: Pubkey,
+    /// Earliest Execution Timestamp
+    pub eta: i64,
+
+    /// Seed Bump
+    pub bump: u8,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, InitSpace, Clone, Debug, PartialEq, Eq)]
+pub enum ConfigChange {
+    /// New members state
+    Members(Pubkey),
+    /// New factory authority PDA
+    Factory(Pubkey),
+    /// New timelock delay in seconds
+    TimelockDelay(i64),
+}
+
+impl ConfigChange {
+    /// Seed byte, one pending change per kind
+    pub fn kind(&self) -> u8 {
+        match self {
+            ConfigChange::Members(_) => 0,
+            ConfigChange::Factory(_) => 1,
+            ConfigChange::TimelockDelay(_) => 2,
+        }
+    }
+}
+
+#[account]
+#[derive(InitSpace)]
+pub struct Reserves {
+    /// Controller
+    pub controller: Pubkey,
+    /// Slot the next attestation is written to
+    pub head: u8,
+    /// Attestations posted so far
+    pub count: u64,
+    /// Attestation Ring Buffer
+    pub attestations: [ReserveAttestation; RESERVE_HISTORY],
+
+    /// Seed Bump
+    pub bump: u8,
+}
+
+impl Reserves {
+    pub fn latest(&self) -> Option<&ReserveAttestation> {
+        if self.count == 0 {
+            return None
+        }
+
+        Some(&self.attestations[(self.head as usize + RESERVE_HISTORY - 1) % RESERVE_HISTORY])
+    }
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, InitSpace, Clone, Copy, Default)]
+pub struct ReserveAttestation {
+    /// BTC held by the custodian, in satoshis
+    pub btc_balance: u64,
+    /// Bitcoin block height of the snapshot
+    pub block_height: u64,
+    /// Time of the attestation
+    pub timestamp: i64,
+    /// Hash of the signed off-chain report
+    pub report_hash: [u8; 32],
+    /// Custodian that posted it
+    pub custodian: Pubkey,
+}
+
+#[account]
+#[derive(InitSpace)]
+pub struct FreezeRecord {
+    /// Controller
+    pub controller: Pubkey,
+    /// Frozen Token Account
+    pub token_account: Pubkey,
+    /// Owner of the token account when frozen
+    pub holder: Pubkey,
+    /// Reason Code
+    pub reason_code: u16,
+    /// Freeze Timestamp
+    pub frozen_at: i64,
+    /// Owner or compliance key that froze the account
+    pub frozen_by: Pubkey,
+
+    /// Seed Bump
+    pub bump: u8,
+}
+
+/// Token-2022 metadata field, mirrors spl_token_metadata_interface::state::Field
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug, PartialEq, Eq)]
+pub enum MetadataField {
+    Name,
+    Symbol,
+    Uri,
+    /// Additional key, e.g. a proof-of-reserves URL
+    Key(String),
+}
+
+impl From<MetadataField> for Field {
+    fn from(field: MetadataField) -> Self {
+        match field {
+            MetadataField::Name => Field::Name,
+            MetadataField::Symbol => Field::Symbol,
+            MetadataField::Uri => Field::Uri,
+            MetadataField::Key(key) => Field::Key(key),
+        }
+    }
+}
+
+#[event]
+pub struct ControllerInitialized {
+    pub controller: Pubkey,
+    pub token_mint: Pubkey,
+    pub owner: Pubkey,
+}
+
+#[event]
+pub struct OwnershipTransferStarted {
+    pub controller: Pubkey,
+    pub owner: Pubkey,
+    pub pending_owner: Pubkey,
+}
+
+#[event]
+pub struct OwnershipTransferred {
+    pub controller: Pubkey,
+    pub previous_owner: Pubkey,
+    /// Default pubkey when renounced
+    pub new_owner: Pubkey,
+}
+
+#[event]
+pub struct MembersSet {
+    pub controller: Pubkey,
+    pub members: Pubkey,
+}
+
+#[event]
+pub struct FactorySet {
+    pub controller: Pubkey,
+    pub factory: Pubkey,
+}
+
+#[event]
+pub struct GuardianSet {
+    pub controller: Pubkey,
+    pub guardian: Pubkey,
+}
+
+#[event]
+pub struct PauserSet {
+    pub controller: Pubkey,
+    pub pauser: Pubkey,
+}
+
+#[event]
+pub struct Paused {
+    pub controller: Pubkey,
+    pub by: Pubkey,
+}
+
+#[event]
+pub struct Unpaused {
+    pub controller: Pubkey,
+    pub by: Pubkey,
+}
+
+#[event]
+pub struct SupplyCapsSet {
+    pub controller: Pubkey,
+    pub max_supply: u64,
+    pub epoch_mint_limit: u64,
+    pub epoch_duration: i64,
+}
+
+#[event]
+pub struct Minted {
+    pub controller: Pubkey,
+    pub to: Pubkey,
+    pub amount: u64,
+}
+
+#[event]
+pub struct Burned {
+    pub controller: Pubkey,
+    pub from: Pubkey,
+    pub amount: u64,
+}
+
+#[event]
+pub struct ComplianceSet {
+    pub controller: Pubkey,
+    pub compliance: Pubkey,
+}
+
+#[event]
+pub struct AccountFrozen {
+    pub controller: Pubkey,
+    pub token_account: Pubkey,
+    pub holder: Pubkey,
+    pub reason_code: u16,
+    pub by: Pubkey,
+}
+
+#[event]
+pub struct AccountThawed {
+    pub controller: Pubkey,
+    pub token_account: Pubkey,
+    pub holder: Pubkey,
+    pub by: Pubkey,
+}
+
+#[event]
+pub struct TokensRecovered {
+    pub controller: Pubkey,
+    pub mint: Pubkey,
+    pub source: Pubkey,
+    pub destination: Pubkey,
+    pub amount: u64,
+}
+
+#[event]
+pub struct MetadataSet {
+    pub controller: Pubkey,
+    pub token_mint: Pubkey,
+    pub name: String,
+    pub symbol: String,
+    pub uri: String,
+}
+
+#[event]
+pub struct MetadataUpdated {
+    pub controller: Pubkey,
+    pub token_mint: Pubkey,
+    pub field: MetadataField,
+    pub value: String,
+}
+
+#[event]
+pub struct ReserveCheckSet {
+    pub controller: Pubkey,
+    pub enabled: bool,
+}
+
+#[event]
+pub struct ReservesAttested {
+    pub controller: Pubkey,
+    pub custodian: Pubkey,
+    pub btc_balance: u64,
+    pub block_height: u64,
+    pub timestamp: i64,
+    pub report_hash: [u8; 32],
+}
+
+#[event]
+pub struct ConfigChangeQueued {
+    pub controller: Pubkey,
+    pub change: ConfigChange,
+    pub eta: i64,
+}
+
+#[event]
+pub struct ConfigChangeCancelled {
+    pub controller: Pubkey,
+    pub change: ConfigChange,
+}
+
+#[event]
+pub struct ConfigChangeExecuted {
+    pub controller: Pubkey,
+    pub change: ConfigChange,
+}
+
+#[event]
+pub struct Migrated {
+    pub account: Pubkey,
+    pub from_version: u8,
+    pub to_version: u8,
 }
 
 #[error_code]
 pub enum Errors {
     #[msg("sender not authorized for minting or burning.")]
     SenderNotAuthorized,
+    #[msg("controller is paused.")]
+    Paused,
+    #[msg("signer is not the pending owner.")]
+    NotPendingOwner,
+    #[msg("mint would exceed the max supply.")]
+    SupplyCapExceeded,
+    #[msg("mint would exceed the epoch mint limit.")]
+    RateLimitExceeded,
+    #[msg("epoch duration must be positive.")]
+    InvalidEpochDuration,
+    #[msg("token metadata requires a Token-2022 mint.")]
+    MetadataRequiresToken2022,
+    #[msg("reserve check is enabled but no reserves account was passed.")]
+    ReservesRequired,
+    #[msg("mint would exceed the attested reserves.")]
+    ReservesExceeded,
+    #[msg("already configured, queue a timelocked change instead.")]
+    TimelockRequired,
+    #[msg("queued change is still timelocked.")]
+    TimelockNotExpired,
+    #[msg("timelock delay can't be negative.")]
+    InvalidTimelockDelay,
+    #[msg("not a Members account owned by the members program.")]
+    InvalidMembers,
+    #[msg("not a factory authority backed by a FactoryState.")]
+    InvalidFactory,
+    #[msg("factory state belongs to a different controller.")]
+    FactoryControllerMismatch,
+    #[msg("account can't be migrated by this program.")]
+    InvalidMigration,
+    #[msg("account is already at the current version.")]
+    AlreadyMigrated,
+    #[msg("token account has the wrong mint or owner.")]
+    InvalidTokenAccount,
+    #[msg("reserves are in satoshis, the mint must have 8 decimals.")]
+    ReservesDecimalsMismatch,
+    #[msg("mint or burn totals would overflow.")]
+    StatsOverflow,
 }
\ No newline at end of file
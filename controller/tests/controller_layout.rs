@@ -0,0 +1,55 @@
+use anchor_lang::prelude::*;
+use anchor_lang::Discriminator;
+use controller::Controller;
+
+fn controller_state(members: Pubkey, guardian: Pubkey) -> Controller {
+    Controller {
+        version: controller::CURRENT_VERSION,
+        owner: Pubkey::new_unique(),
+        pending_owner: Pubkey::new_unique(),
+        token_mint: Pubkey::new_unique(),
+        members,
+        factory: Pubkey::new_unique(),
+        paused: true,
+        pauser: Pubkey::new_unique(),
+        mint_authority_bump: 255,
+        max_supply: u64::MAX,
+        epoch_mint_limit: u64::MAX,
+        epoch_duration: i64::MAX,
+        epoch_start: i64::MAX,
+        epoch_minted: u64::MAX,
+        compliance: Pubkey::new_unique(),
+        freeze_authority_bump: 254,
+        enforce_reserves: true,
+        timelock_delay: i64::MAX,
+        bump: 253,
+        total_minted: u64::MAX,
+        total_burned: u64::MAX,
+        mint_count: u64::MAX,
+        burn_count: u64::MAX,
+        guardian,
+        prev_epoch_minted: u64::MAX,
+    }
+}
+
+#[test]
+fn members_reads_the_controller_discriminator() {
+    assert_eq!(members::CONTROLLER_DISCRIMINATOR, Controller::DISCRIMINATOR);
+}
+
+#[test]
+fn members_reads_members_and_guardian_at_their_offsets() {
+    let (members, guardian) = (Pubkey::new_unique(), Pubkey::new_unique());
+
+    let mut data = Vec::new();
+    controller_state(members, guardian).try_serialize(&mut data).unwrap();
+
+    assert_eq!(data[..8], members::CONTROLLER_DISCRIMINATOR);
+    assert_eq!(data[members::CONTROLLER_MEMBERS_RANGE], members.to_bytes());
+    assert_eq!(data[members::CONTROLLER_GUARDIAN_RANGE], guardian.to_bytes());
+}
+
+#[test]
+fn members_pins_the_controller_program() {
+    assert_eq!(members::controller_program::ID, controller::ID);
+}
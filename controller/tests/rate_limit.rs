@@ -0,0 +1,187 @@
+use anchor_lang::prelude::*;
+use anchor_lang::solana_program::entrypoint::ProgramResult;
+use anchor_lang::solana_program::instruction::Instruction;
+use anchor_lang::solana_program::program_option::COption;
+use anchor_lang::{InstructionData, ToAccountMetas};
+use test_runtime::{Entry, Runtime, NOW};
+use controller::{Controller, Errors};
+use spl_token::state::{Account as TokenAccount, AccountState, Mint};
+
+const DURATION: i64 = 100;
+const LIMIT: u64 = 1_000;
+
+fn err(error: impl Into<anchor_lang::error::Error>) -> ProgramError {
+    error.into().into()
+}
+
+struct Fixture {
+    runtime: Runtime,
+    controller_state: Pubkey,
+    token_mint: Pubkey,
+    tokens: Pubkey,
+    factory: Pubkey,
+}
+
+impl Fixture {
+    /// A controller allowing LIMIT per DURATION seconds, its first bucket starting at NOW
+    fn new() -> Self {
+        let mut runtime = Runtime::new(&[(controller::ID, controller::entry as Entry)]);
+
+        let controller_state = Pubkey::new_unique();
+        let token_mint = Pubkey::new_unique();
+        let tokens = Pubkey::new_unique();
+        let factory = Pubkey::new_unique();
+        runtime.fund(factory);
+
+        let (mint_authority, mint_authority_bump) = Pubkey::find_program_address(&[b"mint_authority", controller_state.as_ref()], &controller::ID);
+
+        runtime.set_state(controller_state, controller::ID, 8 + Controller::INIT_SPACE, &Controller {
+            version: controller::CURRENT_VERSION,
+            owner: Pubkey::new_unique(),
+            pending_owner: Pubkey::default(),
+            token_mint,
+            members: Pubkey::new_unique(),
+            factory,
+            paused: false,
+            pauser: Pubkey::default(),
+            mint_authority_bump,
+            max_supply: 0,
+            epoch_mint_limit: LIMIT,
+            epoch_duration: DURATION,
+            epoch_start: NOW,
+            epoch_minted: 0,
+            compliance: Pubkey::default(),
+            freeze_authority_bump: 0,
+            enforce_reserves: false,
+            timelock_delay: controller::DEFAULT_TIMELOCK_DELAY,
+            bump: 0,
+            total_minted: 0,
+            total_burned: 0,
+            mint_count: 0,
+            burn_count: 0,
+            guardian: Pubkey::default(),
+            prev_epoch_minted: 0,
+        });
+
+        runtime.set_pack(token_mint, spl_token::ID, Mint {
+            mint_authority: COption::Some(mint_authority),
+            supply: 0,
+            decimals: 8,
+            is_initialized: true,
+            freeze_authority: COption::None,
+        });
+
+        runtime.set_pack(tokens, spl_token::ID, TokenAccount {
+            mint: token_mint,
+            owner: Pubkey::new_unique(),
+            amount: 0,
+            delegate: COption::None,
+            state: AccountState::Initialized,
+            is_native: COption::None,
+            delegated_amount: 0,
+            close_authority: COption::None,
+        });
+
+        Fixture {
+            runtime,
+            controller_state,
+            token_mint,
+            tokens,
+            factory,
+        }
+    }
+
+    fn mint_at(&mut self, unix_timestamp: i64, amount: u64) -> ProgramResult {
+        self.runtime.warp(unix_timestamp);
+
+        let accounts = controller::accounts::MintCtx {
+            controller_state: self.controller_state,
+            token_mint: self.token_mint,
+            to_token_account: self.tokens,
+            recipient: None,
+            payer: None,
+            mint_authority: Pubkey::find_program_address(&[b"mint_authority", self.controller_state.as_ref()], &controller::ID).0,
+            signer: self.factory,
+            reserves: None,
+            token_program: spl_token::ID,
+            associated_token_program: None,
+            system_program: None,
+        };
+
+        self.runtime.process(Instruction {
+            program_id: controller::ID,
+            accounts: accounts.to_account_metas(None),
+            data: controller::instruction::Mint { amount }.data(),
+        })
+    }
+
+    fn controller(&self) -> Controller {
+        self.runtime.state(&self.controller_state)
+    }
+}
+
+#[test]
+fn mints_in_the_same_bucket_add_up_to_the_limit() {
+    let mut fixture = Fixture::new();
+
+    fixture.mint_at(NOW, 600).unwrap();
+    fixture.mint_at(NOW + DURATION - 1, 400).unwrap();
+    assert_eq!(fixture.mint_at(NOW + DURATION - 1, 1), Err(err(Errors::RateLimitExceeded)));
+
+    let controller = fixture.controller();
+    assert_eq!(controller.epoch_start, NOW);
+    assert_eq!(controller.epoch_minted, LIMIT);
+    assert_eq!(controller.prev_epoch_minted, 0);
+    assert_eq!(fixture.runtime.unpack::<TokenAccount>(&fixture.tokens).amount, LIMIT);
+}
+
+#[test]
+fn previous_bucket_carries_over_weighted_by_its_overlap() {
+    let mut fixture = Fixture::new();
+    fixture.mint_at(NOW, LIMIT).unwrap();
+
+    // A quarter into the next bucket, three quarters of the previous one still count
+    let now = NOW + DURATION + DURATION / 4;
+    let carried = LIMIT * 3 / 4;
+    assert_eq!(fixture.mint_at(now, LIMIT - carried + 1), Err(err(Errors::RateLimitExceeded)));
+    fixture.mint_at(now, LIMIT - carried).unwrap();
+
+    let controller = fixture.controller();
+    assert_eq!(controller.epoch_start, NOW + DURATION);
+    assert_eq!(controller.epoch_minted, LIMIT - carried);
+    assert_eq!(controller.prev_epoch_minted, LIMIT);
+}
+
+#[test]
+fn window_resets_after_two_idle_buckets() {
+    let mut fixture = Fixture::new();
+    fixture.mint_at(NOW, LIMIT).unwrap();
+
+    let now = NOW + 2 * DURATION;
+    fixture.mint_at(now, LIMIT).unwrap();
+
+    let controller = fixture.controller();
+    assert_eq!(controller.epoch_start, now);
+    assert_eq!(controller.epoch_minted, LIMIT);
+    assert_eq!(controller.prev_epoch_minted, 0);
+}
+
+#[test]
+fn bucket_rolls_over_exactly_at_its_end() {
+    let mut fixture = Fixture::new();
+    fixture.mint_at(NOW, LIMIT).unwrap();
+
+    // At epoch_start + duration the new bucket has begun but the old one still fully counts
+    let boundary = NOW + DURATION;
+    assert_eq!(fixture.mint_at(boundary, 1), Err(err(Errors::RateLimitExceeded)));
+
+    fixture.mint_at(boundary, 0).unwrap();
+    let controller = fixture.controller();
+    assert_eq!(controller.epoch_start, boundary);
+    assert_eq!(controller.epoch_minted, 0);
+    assert_eq!(controller.prev_epoch_minted, LIMIT);
+
+    // One second later a hundredth of the old bucket has slid out of the window
+    fixture.mint_at(boundary + 1, LIMIT / 100).unwrap();
+    assert_eq!(fixture.mint_at(boundary + 1, 1), Err(err(Errors::RateLimitExceeded)));
+}
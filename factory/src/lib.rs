@@ -1,103 +1,158 @@
 use anchor_lang::prelude::*;
+use anchor_lang::Discriminator;
 use controller::{self, Controller, MintCtx, BurnCtx};
-use members::{self, Members, Merchant};
+use members::{self, Custodian, Members, Merchant};
 use anchor_lang::solana_program::clock::{self, Clock};
-use anchor_spl::token::{self, TokenAccount, Mint, Token};
+use anchor_lang::solana_program::hash::hash;
+use anchor_spl::token_interface::{TokenAccount, Mint, TokenInterface};
+use anchor_spl::associated_token::AssociatedToken;
+use versioning::Upgrade;
 
-declare_id!("Fg6PaFpoGXkYsidMpWTK6W2BeZ7FEfcYkg476zPFsLnS");
+declare_id!("23ZpMy4UBi28oSN8XSkpVUr45GWPL8jXXzGXJjARhswL");
 
-fn is_merchant(
+/// Factory layout version, see `members::CURRENT_VERSION` for the policy
+pub const CURRENT_VERSION: u8 = 2;
+
+/// Size unversioned FactoryState accounts were allocated with
+const LEGACY_FACTORY_STATE_SPACE: usize = 1000;
+
+/// Size unversioned DepositAddress accounts were allocated with
+const LEGACY_DEPOSIT_ADDRESS_SPACE: usize = 100;
+
+/// Size unversioned Request accounts were allocated with
+const LEGACY_REQUEST_SPACE: usize = 1000;
+
+const _: () = assert!(8 + FactoryState::INIT_SPACE != LEGACY_FACTORY_STATE_SPACE);
+const _: () = assert!(8 + DepositAddress::INIT_SPACE != LEGACY_DEPOSIT_ADDRESS_SPACE);
+const _: () = assert!(8 + Request::INIT_SPACE != LEGACY_REQUEST_SPACE);
+
+/// Max length of a BTC deposit address, keep in sync with its `max_len`
+pub const MAX_DEPOSIT_ADDRESS_LEN: usize = 90;
+
+/// Max length of a BTC txid, keep in sync with its `max_len`
+pub const MAX_TXID_LEN: usize = 64;
+
+/// Mint request seed for `txid`, hashed since seeds are capped at 32 bytes
+pub fn txid_seed(txid: &str) -> [u8; 32] {
+    hash(txid.as_bytes()).to_bytes()
+}
+
+/// Removed and frozen merchants are locked out of every merchant path
+pub fn check_merchant_status(merchant_state: &Merchant) -> Result<()> {
+    if !merchant_state.active {
+        return Err(Errors::MerchantInactive.into())
+    }
+
+    if merchant_state.frozen {
+        return Err(Errors::MerchantFrozen.into())
+    }
+
+    Ok(())
+}
+
+fn check_merchant(
     merchant: Pubkey,
-    merchant_state: Account<Merchant>,
-    member_state: Pubkey,
-    members: Pubkey
-) -> bool {
+    merchant_state: &Account<Merchant>,
+    member_state: Pubkey
+) -> Result<()> {
     let merchant_state_pubkey = Pubkey::find_program_address(&[
         b"merchant".as_ref(),
-        member_state.key().as_ref(),
-        merchant.key().as_ref()
-    ], &members);
-
-    if merchant_state.key() == merchant_state_pubkey.0 {
-        return true
-    } else {
-        return false
+        member_state.as_ref(),
+        merchant.as_ref()
+    ], &members::ID);
+
+    if merchant_state.key() != merchant_state_pubkey.0 {
+        return Err(Errors::SenderNotAuthorized.into())
     }
+
+    check_merchant_status(merchant_state)
 }
 
 #[program]
 pub mod factory {
-    use anchor_spl::token::Burn;
-
     use super::*;
 
     pub fn initialize(ctx: Context<Initialize>) -> Result<()> {
         let factory = &mut ctx.accounts.factory_state;
+        factory.version = CURRENT_VERSION;
         factory.controller_program = ctx.accounts.controller_program.key();
         factory.controller_state = ctx.accounts.controller_state.key();
-        factory.admin = ctx.accounts.controller_program.key();
+        factory.admin = ctx.accounts.admin.key();
         factory.mint_request_count = 0;
         factory.burn_request_count = 0;
+        factory.authority_bump = *ctx.bumps.get("factory_authority").unwrap();
+        factory.bump = *ctx.bumps.get("factory_state").unwrap();
+
+        emit!(FactoryInitialized {
+            factory_state: factory.key(),
+            controller_state: factory.controller_state,
+            admin: factory.admin,
+        });
 
         Ok(())
     }
 
     pub fn set_custodian_deposit_address(ctx: Context<SetCustodianDepositAddress>, deposit_address: String) -> Result<()> {
-        let members_state = ctx.accounts.member_state.clone();
+        check_merchant(ctx.accounts.merchant.key(), &ctx.accounts.merchant_state, ctx.accounts.member_state.key())?;
 
-        if !is_merchant(ctx.accounts.merchant.key(), ctx.accounts.merchant_state.clone(), ctx.accounts.members.key(), ctx.accounts.member_state.key().clone()) {
-            return Err(Errors::SenderNotAuthorized.into())
-        }
-
-        if members_state.custodian == ctx.accounts.signer.key() {
-            return Err(Errors::SenderNotAuthorized.into())
-        }
-
-        if deposit_address.len() == 0 {
+        if deposit_address.len() == 0 || deposit_address.len() > MAX_DEPOSIT_ADDRESS_LEN {
             return Err(Errors::InvalidDepositAddress.into())
         }
         
         let custodian_deposit_address = &mut ctx.accounts.custodian_deposit_address;
+        custodian_deposit_address.version = CURRENT_VERSION;
         custodian_deposit_address.address = deposit_address;
         custodian_deposit_address.bump = *ctx.bumps.get("custodian_deposit_address").unwrap();
 
+        emit!(DepositAddressSet {
+            factory_state: ctx.accounts.factory_state.key(),
+            merchant: ctx.accounts.merchant.key(),
+            custodian: true,
+            address: custodian_deposit_address.address.clone(),
+        });
+
         Ok(())
     }
 
     pub fn set_merchant_deposit_address(ctx: Context<SetMerchantDepositAddress>, deposit_address: String) -> Result<()> {
-        if !is_merchant(ctx.accounts.merchant.key(), ctx.accounts.merchant_state.clone(), ctx.accounts.members.key(), ctx.accounts.member_state.key().clone()) {
-            return Err(Errors::SenderNotAuthorized.into())
-        }
+        check_merchant(ctx.accounts.merchant.key(), &ctx.accounts.merchant_state, ctx.accounts.member_state.key())?;
 
-        if deposit_address.len() == 0 {
+        if deposit_address.len() == 0 || deposit_address.len() > MAX_DEPOSIT_ADDRESS_LEN {
             return Err(Errors::InvalidDepositAddress.into())
         }
         
         let merchant_deposit_address = &mut ctx.accounts.merchant_deposit_address;
+        merchant_deposit_address.version = CURRENT_VERSION;
         merchant_deposit_address.address = deposit_address;
         merchant_deposit_address.bump = *ctx.bumps.get("merchant_deposit_address").unwrap();
 
+        emit!(DepositAddressSet {
+            factory_state: ctx.accounts.factory_state.key(),
+            merchant: ctx.accounts.merchant.key(),
+            custodian: false,
+            address: merchant_deposit_address.address.clone(),
+        });
+
         Ok(())
     }
 
     pub fn add_mint_request(ctx: Context<AddMintRequest>, txid: String, deposit_address: String, amount: u64) -> Result<()> {
         let factory_state = &mut ctx.accounts.factory_state;
 
-        if !is_merchant(ctx.accounts.merchant.key(), ctx.accounts.merchant_state.clone(), ctx.accounts.members.key(), ctx.accounts.member_state.key().clone()) {
-            return Err(Errors::SenderNotAuthorized.into())
-        }
+        check_merchant(ctx.accounts.merchant.key(), &ctx.accounts.merchant_state, ctx.accounts.member_state.key())?;
 
         if deposit_address != ctx.accounts.custodian_deposit_address.address.to_string() {
             return Err(Errors::InvalidDepositAddress.into())
         }
 
-        if txid.len() == 0 {
+        if txid.len() == 0 || txid.len() > MAX_TXID_LEN {
             return Err(Errors::InvalidTxid.into())
         }
 
         let timestamp: u64 = clock::Clock::get().unwrap().unix_timestamp.try_into().unwrap();
 
         let mint_request = &mut ctx.accounts.request;
+        mint_request.version = CURRENT_VERSION;
         mint_request.requester = ctx.accounts.merchant.key();
         mint_request.amount = amount;
         mint_request.deposit_address = deposit_address;
@@ -108,21 +163,44 @@ pub mod factory {
         mint_request.status = 0; // PENDING
         mint_request.bump = *ctx.bumps.get("request").unwrap();
 
+        emit!(MintRequestAdded {
+            factory_state: factory_state.key(),
+            request: mint_request.key(),
+            requester: mint_request.requester,
+            nonce: mint_request.nonce,
+            amount,
+            txid: mint_request.txid.clone(),
+            deposit_address: mint_request.deposit_address.clone(),
+            timestamp,
+        });
+
         Ok(())
     }
     
     pub fn cancel_mint_request(ctx: Context<CancelMintRequest>, txid: String) -> Result<()> {
-        if !is_merchant(ctx.accounts.merchant.key(), ctx.accounts.merchant_state.clone(), ctx.accounts.members.key(), ctx.accounts.member_state.key().clone()) {
-            return Err(Errors::SenderNotAuthorized.into())
-        }
+        check_merchant(ctx.accounts.merchant.key(), &ctx.accounts.merchant_state, ctx.accounts.member_state.key())?;
 
         if txid.len() == 0 {
             return Err(Errors::InvalidTxid.into())
         }
 
         let mint_request = &mut ctx.accounts.request;
+        if mint_request.requester != ctx.accounts.merchant.key() {
+            return Err(Errors::SenderNotAuthorized.into())
+        }
+
+        if mint_request.status != 0 {
+            return Err(Errors::RequestNotPending.into())
+        }
         mint_request.status = 1; // CANCELLED
 
+        emit!(MintRequestCancelled {
+            factory_state: ctx.accounts.factory_state.key(),
+            request: mint_request.key(),
+            nonce: mint_request.nonce,
+            txid,
+        });
+
         Ok(())
     }
 
@@ -131,26 +209,57 @@ pub mod factory {
             return Err(Errors::InvalidTxid.into())
         }
 
+        // The merchant may have been removed or frozen since it filed the request
+        check_merchant_status(&ctx.accounts.requester_state)?;
+
         let mint_request = &mut ctx.accounts.request;
+        if mint_request.status != 0 {
+            return Err(Errors::RequestNotPending.into())
+        }
         mint_request.status = 2; // APPROVED
+        mint_request.custodian = ctx.accounts.custodian.key();
 
         {
+            let factory_key = ctx.accounts.factory_state.key();
+            let seeds = &[
+                b"factory_authority".as_ref(),
+                factory_key.as_ref(),
+                &[ctx.accounts.factory_state.authority_bump],
+            ];
+            let signer = &[&seeds[..]];
+
             let cpi_accounts = controller::cpi::accounts::MintCtx {
                 controller_state: ctx.accounts.controller_state.to_account_info(),
                 token_mint: ctx.accounts.token_mint.to_account_info(),
                 to_token_account: ctx.accounts.token_account.to_account_info(),
-                signer: ctx.accounts.factory_program.clone(),
+                recipient: Some(ctx.accounts.requester.to_account_info()),
+                payer: ctx.accounts.payer.as_ref().map(|payer| payer.to_account_info()),
+                mint_authority: ctx.accounts.mint_authority.to_account_info(),
+                signer: ctx.accounts.factory_authority.to_account_info(),
+                reserves: ctx.accounts.reserves.as_ref().map(|reserves| reserves.to_account_info()),
                 token_program: ctx.accounts.token_program.to_account_info(),
+                associated_token_program: ctx.accounts.associated_token_program.as_ref().map(|program| program.to_account_info()),
+                system_program: ctx.accounts.system_program.as_ref().map(|program| program.to_account_info()),
             };
     
-            let cpi_ctx = CpiContext::new(
-                ctx.accounts.factory_program.to_account_info(),
+            let cpi_ctx = CpiContext::new_with_signer(
+                ctx.accounts.controller_program.to_account_info(),
                 cpi_accounts,
+                signer,
             );
             
-            controller::cpi::mint(cpi_ctx, mint_request.amount);
+            controller::cpi::mint(cpi_ctx, mint_request.amount)?;
         }
 
+        emit!(MintRequestConfirmed {
+            factory_state: ctx.accounts.factory_state.key(),
+            request: mint_request.key(),
+            nonce: mint_request.nonce,
+            amount: mint_request.amount,
+            txid,
+            custodian: mint_request.custodian,
+        });
+
         Ok(())
     }
 
@@ -160,21 +269,30 @@ pub mod factory {
         }
 
         let mint_request = &mut ctx.accounts.request;
+        if mint_request.status != 0 {
+            return Err(Errors::RequestNotPending.into())
+        }
         mint_request.status = 3; // REJECTED
 
+        emit!(MintRequestRejected {
+            factory_state: ctx.accounts.factory_state.key(),
+            request: mint_request.key(),
+            nonce: mint_request.nonce,
+            txid,
+        });
+
         Ok(())
     }
 
     pub fn add_burn_request(ctx: Context<AddBurnRequest>, amount: u64) -> Result<()> {
         let factory_state = &mut ctx.accounts.factory_state;
 
-        if !is_merchant(ctx.accounts.merchant.key(), ctx.accounts.merchant_state.clone(), ctx.accounts.members.key(), ctx.accounts.member_state.key().clone()) {
-            return Err(Errors::SenderNotAuthorized.into())
-        }
+        check_merchant(ctx.accounts.merchant.key(), &ctx.accounts.merchant_state, ctx.accounts.member_state.key())?;
 
         let timestamp: u64 = clock::Clock::get().unwrap().unix_timestamp.try_into().unwrap();
 
         let burn_request = &mut ctx.accounts.request;
+        burn_request.version = CURRENT_VERSION;
         burn_request.requester = ctx.accounts.merchant.key();
         burn_request.amount = amount;
         burn_request.txid = "".to_string(); // set txid as empty since it is not known yet
@@ -182,30 +300,176 @@ pub mod factory {
         factory_state.burn_request_count += 1;
         burn_request.timestamp = timestamp;
         burn_request.status = 0; // PENDING
+        burn_request.bump = *ctx.bumps.get("request").unwrap();
 
         {
-            let cpi_accounts = Burn {
-                mint: ctx.accounts.token_mint.to_account_info(),
-                from: ctx.accounts.token_account.to_account_info(),
-                authority: ctx.accounts.merchant.to_account_info(),
+            let factory_key = factory_state.key();
+            let seeds = &[
+                b"factory_authority".as_ref(),
+                factory_key.as_ref(),
+                &[factory_state.authority_bump],
+            ];
+            let signer = &[&seeds[..]];
+
+            let cpi_accounts = controller::cpi::accounts::BurnCtx {
+                controller_state: ctx.accounts.controller_state.to_account_info(),
+                token_mint: ctx.accounts.token_mint.to_account_info(),
+                from_token_account: ctx.accounts.token_account.to_account_info(),
+                owner: ctx.accounts.merchant.to_account_info(),
+                signer: ctx.accounts.factory_authority.to_account_info(),
+                token_program: ctx.accounts.token_program.to_account_info(),
             };
     
-            let cpi_ctx = CpiContext::new(
-                ctx.accounts.token_program.to_account_info(),
+            let cpi_ctx = CpiContext::new_with_signer(
+                ctx.accounts.controller_program.to_account_info(),
                 cpi_accounts,
+                signer,
             );
             
-            token::burn(cpi_ctx, amount)?;
+            controller::cpi::burn(cpi_ctx, amount)?;
         }
 
+        emit!(BurnRequestAdded {
+            factory_state: factory_state.key(),
+            request: burn_request.key(),
+            requester: burn_request.requester,
+            nonce: burn_request.nonce,
+            amount,
+            deposit_address: ctx.accounts.merchant_deposit_address.address.clone(),
+            timestamp,
+        });
+
         Ok(())
     }
 
-    pub fn confirm_burn_request(ctx: Context<ConfirmBurnRequest>, nonce: u8, txid: String) -> Result<()> {
+    pub fn confirm_burn_request(ctx: Context<ConfirmBurnRequest>, nonce: u128, txid: String) -> Result<()> {
+        if txid.len() > MAX_TXID_LEN {
+            return Err(Errors::InvalidTxid.into())
+        }
+
         let burn_request = &mut ctx.accounts.request;
+        if burn_request.status != 0 {
+            return Err(Errors::RequestNotPending.into())
+        }
 
         burn_request.txid = txid;
         burn_request.status = 2; // APPROVED
+        burn_request.custodian = ctx.accounts.custodian.key();
+
+        emit!(BurnRequestConfirmed {
+            factory_state: ctx.accounts.factory_state.key(),
+            request: burn_request.key(),
+            nonce: burn_request.nonce,
+            txid: burn_request.txid.clone(),
+            custodian: burn_request.custodian,
+        });
+
+        Ok(())
+    }
+
+    pub fn block_mint_request(ctx: Context<BlockMintRequest>, txid: String) -> Result<()> {
+        let mint_request = &mut ctx.accounts.request;
+        if mint_request.status != 0 {
+            return Err(Errors::RequestNotPending.into())
+        }
+        mint_request.status = 4; // BLOCKED
+
+        emit!(MintRequestBlocked {
+            factory_state: ctx.accounts.factory_state.key(),
+            request: mint_request.key(),
+            nonce: mint_request.nonce,
+            txid,
+            by: ctx.accounts.signer.key(),
+        });
+
+        Ok(())
+    }
+
+    pub fn get_request(ctx: Context<GetRequest>, kind: RequestKind, nonce: u128) -> Result<RequestInfo> {
+        let factory_key = ctx.accounts.factory_state.key();
+        let request = &ctx.accounts.request;
+
+        // Mint requests are keyed by txid, burn requests by nonce
+        let (expected, _) = match kind {
+            RequestKind::Mint => Pubkey::find_program_address(&[
+                b"mint_request".as_ref(),
+                factory_key.as_ref(),
+                txid_seed(&request.txid).as_ref()
+            ], &crate::ID),
+            RequestKind::Burn => Pubkey::find_program_address(&[
+                b"burn_request".as_ref(),
+                factory_key.as_ref(),
+                nonce.to_string().as_ref()
+            ], &crate::ID),
+        };
+
+        if request.key() != expected || request.nonce != nonce {
+            return Err(Errors::InvalidRequest.into())
+        }
+
+        Ok(RequestInfo {
+            requester: request.requester,
+            amount: request.amount,
+            deposit_address: request.deposit_address.clone(),
+            txid: request.txid.clone(),
+            nonce: request.nonce,
+            timestamp: request.timestamp,
+            status: request.status,
+            custodian: request.custodian,
+        })
+    }
+
+    pub fn migrate(ctx: Context<Migrate>) -> Result<()> {
+        let account = &ctx.accounts.account;
+        if account.owner != &crate::ID {
+            return Err(Errors::InvalidMigration.into())
+        }
+
+        let (upgrade, space) = {
+            let data = account.try_borrow_data()?;
+            if data.len() < 9 {
+                return Err(Errors::InvalidMigration.into())
+            }
+
+            if data[..8] == FactoryState::DISCRIMINATOR && data.len() == LEGACY_FACTORY_STATE_SPACE {
+                let legacy = FactoryStateV0::deserialize(&mut &data[8..]).map_err(|_| Errors::InvalidMigration)?;
+
+                (Upgrade::rewrite(&legacy.upgrade(account.key()))?, 8 + FactoryState::INIT_SPACE)
+            } else if data[..8] == DepositAddress::DISCRIMINATOR && data.len() == LEGACY_DEPOSIT_ADDRESS_SPACE {
+                let legacy = DepositAddressV0::deserialize(&mut &data[8..]).map_err(|_| Errors::InvalidMigration)?;
+
+                (Upgrade::rewrite(&legacy.upgrade())?, 8 + DepositAddress::INIT_SPACE)
+            } else if data[..8] == Request::DISCRIMINATOR && data.len() == LEGACY_REQUEST_SPACE {
+                let legacy = RequestV0::deserialize(&mut &data[8..]).map_err(|_| Errors::InvalidMigration)?;
+
+                (Upgrade::rewrite(&legacy.upgrade())?, 8 + Request::INIT_SPACE)
+            } else {
+                let space = if data[..8] == FactoryState::DISCRIMINATOR {
+                    8 + FactoryState::INIT_SPACE
+                } else if data[..8] == DepositAddress::DISCRIMINATOR {
+                    8 + DepositAddress::INIT_SPACE
+                } else if data[..8] == Request::DISCRIMINATOR {
+                    8 + Request::INIT_SPACE
+                } else {
+                    return Err(Errors::InvalidMigration.into())
+                };
+
+                (Upgrade::Grow(data[8]), space)
+            }
+        };
+
+        let from_version = upgrade.from_version();
+        if from_version >= CURRENT_VERSION {
+            return Err(Errors::AlreadyMigrated.into())
+        }
+
+        versioning::apply(account, &ctx.accounts.payer, &ctx.accounts.system_program, upgrade, space, CURRENT_VERSION)?;
+
+        emit!(Migrated {
+            account: account.key(),
+            from_version,
+            to_version: CURRENT_VERSION,
+        });
 
         Ok(())
     }
@@ -215,16 +479,38 @@ pub mod factory {
 pub struct Initialize<'info> {
     #[account(
         init,
-        space = 1000,
+        seeds = [
+            b"factory".as_ref(),
+            controller_state.key().as_ref()
+        ],
+        bump,
+        space = 8 + FactoryState::INIT_SPACE,
         payer = payer
     )]
     pub factory_state: Account<'info, FactoryState>,
 
-    #[account(mut)]
+    /// Controller owner, so nobody can front-run the controller's factory
+    #[account(
+        mut,
+        constraint = payer.key() == controller_state.owner @ Errors::SenderNotAuthorized
+    )]
     pub payer: Signer<'info>,
 
+    /// CHECK: Admin key, e.g. the signer PDA of a members multisig
+    pub admin: UncheckedAccount<'info>,
+
     pub controller_state: Account<'info, Controller>,
 
+    /// CHECK: PDA the factory signs with when calling into the controller
+    #[account(
+        seeds = [
+            b"factory_authority".as_ref(),
+            factory_state.key().as_ref()
+        ],
+        bump,
+    )]
+    pub factory_authority: UncheckedAccount<'info>,
+
     pub system_program: Program<'info, System>,
     pub controller_program: UncheckedAccount<'info>
 }
@@ -234,12 +520,16 @@ pub struct Initialize<'info> {
 pub struct SetCustodianDepositAddress<'info> {
     #[account(
         mut,
+        has_one = controller_state
     )]
     pub factory_state: Account<'info, FactoryState>,
 
+    pub controller_state: Account<'info, Controller>,
+
     pub merchant: UncheckedAccount<'info>,
     pub merchant_state: Account<'info, Merchant>,
-    pub members: UncheckedAccount<'info>,
+
+    #[account(address = controller_state.members)]
     pub member_state: Account<'info, Members>,
 
     #[account(
@@ -251,13 +541,26 @@ pub struct SetCustodianDepositAddress<'info> {
         ],
         bump,
         payer = signer,
-        space = 100
+        space = 8 + DepositAddress::INIT_SPACE
     )]
     pub custodian_deposit_address: Account<'info, DepositAddress>,
 
     #[account(mut)]
     pub signer: Signer<'info>,    
 
+    /// Registry entry of the signing custodian
+    #[account(
+        seeds = [
+            b"custodian".as_ref(),
+            controller_state.members.as_ref(),
+            signer.key().as_ref()
+        ],
+        seeds::program = members::ID,
+        bump = custodian_state.bump,
+        constraint = custodian_state.active && custodian_state.allowed_assets.contains(&controller_state.token_mint) @ Errors::CustodianNotAllowed
+    )]
+    pub custodian_state: Account<'info, Custodian>,
+
     pub system_program: Program<'info, System>,
 }
 
@@ -265,11 +568,15 @@ pub struct SetCustodianDepositAddress<'info> {
 pub struct SetMerchantDepositAddress<'info> {
     #[account(
         mut,
+        has_one = controller_state
     )]
     pub factory_state: Account<'info, FactoryState>,
 
+    pub controller_state: Account<'info, Controller>,
+
     pub merchant_state: Account<'info, Merchant>,
-    pub members: UncheckedAccount<'info>,
+
+    #[account(address = controller_state.members)]
     pub member_state: Account<'info, Members>,
 
     #[account(
@@ -281,7 +588,7 @@ pub struct SetMerchantDepositAddress<'info> {
         ],
         bump,
         payer = merchant,
-        space = 100
+        space = 8 + DepositAddress::INIT_SPACE
     )]
     pub merchant_deposit_address: Account<'info, DepositAddress>,
 
@@ -291,16 +598,40 @@ pub struct SetMerchantDepositAddress<'info> {
     pub system_program: Program<'info, System>,
 }
 
+#[derive(Accounts)]
+pub struct GetRequest<'info> {
+    pub factory_state: Account<'info, FactoryState>,
+
+    pub request: Account<'info, Request>,
+}
+
+#[derive(Accounts)]
+pub struct Migrate<'info> {
+    /// CHECK: Owner and discriminator are checked in migrate
+    #[account(mut)]
+    pub account: UncheckedAccount<'info>,
+
+    /// Funds the grown account, any signer can migrate
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    pub system_program: Program<'info, System>
+}
+
 #[derive(Accounts)]
 #[instruction(txid: String)]
 pub struct AddMintRequest<'info> {
     #[account(
         mut,
+        has_one = controller_state
     )]
     pub factory_state: Account<'info, FactoryState>,
 
+    pub controller_state: Account<'info, Controller>,
+
     pub merchant_state: Account<'info, Merchant>,
-    pub members: UncheckedAccount<'info>,
+
+    #[account(address = controller_state.members)]
     pub member_state: Account<'info, Members>,
 
     #[account(
@@ -308,11 +639,11 @@ pub struct AddMintRequest<'info> {
         seeds = [
             b"mint_request".as_ref(),
             factory_state.key().as_ref(),
-            txid.as_ref()
+            txid_seed(&txid).as_ref()
         ],
         bump,
         payer = merchant,
-        space = 1000
+        space = 8 + Request::INIT_SPACE
     )]
     pub request: Account<'info, Request>,
 
@@ -338,18 +669,23 @@ pub struct AddMintRequest<'info> {
 pub struct CancelMintRequest<'info> {
     #[account(
         mut,
+        has_one = controller_state
     )]
     pub factory_state: Account<'info, FactoryState>,
 
+    pub controller_state: Account<'info, Controller>,
+
     pub merchant_state: Account<'info, Merchant>,
-    pub members: UncheckedAccount<'info>,
+
+    #[account(address = controller_state.members)]
     pub member_state: Account<'info, Members>,
 
     #[account(
+        mut,
         seeds = [
             b"mint_request".as_ref(),
             factory_state.key().as_ref(),
-            txid.as_ref()
+            txid_seed(&txid).as_ref()
         ],
         bump = request.bump,
     )]
@@ -364,39 +700,90 @@ pub struct CancelMintRequest<'info> {
 pub struct ConfirmMintRequest<'info> {
     #[account(
         mut,
-        has_one = admin,
         has_one = controller_state,
         has_one = controller_program
     )]
     pub factory_state: Account<'info, FactoryState>,
 
     #[account(
-        constraint = controller_state.factory == factory_program.key()
+        mut,
+        constraint = controller_state.factory == factory_authority.key()
     )]
     pub controller_state: Account<'info, Controller>,
-    pub controller_program: AccountInfo<'info>,
+    pub controller_program: Program<'info, controller::program::Controller>,
 
-    pub token_mint: Account<'info, Mint>,
+    #[account(mut)]
+    pub token_mint: InterfaceAccount<'info, Mint>,
+    /// CHECK: Requester's token account, mint and owner verified by the controller,
+    /// which creates the associated token account when a payer is given
+    #[account(mut)]
+    pub token_account: UncheckedAccount<'info>,
+
+    /// CHECK: Owner of the minted tokens
     #[account(
-        mut,
-        constraint = token_account.owner == request.requester
+        constraint = requester.key() == request.requester @ Errors::SenderNotAuthorized
     )]
-    pub token_account: Account<'info, TokenAccount>,
+    pub requester: UncheckedAccount<'info>,
 
     #[account(
+        seeds = [
+            b"merchant".as_ref(),
+            controller_state.members.as_ref(),
+            requester.key().as_ref()
+        ],
+        seeds::program = members::ID,
+        bump = requester_state.bump
+    )]
+    pub requester_state: Account<'info, Merchant>,
+
+    #[account(mut)]
+    pub payer: Option<Signer<'info>>,
+
+    /// CHECK: Controller's mint authority PDA, verified by the controller
+    pub mint_authority: UncheckedAccount<'info>,
+
+    /// CHECK: Controller's reserves account, verified by the controller
+    pub reserves: Option<UncheckedAccount<'info>>,
+
+    #[account(
+        mut,
         seeds = [
             b"mint_request".as_ref(),
             factory_state.key().as_ref(),
-            txid.as_ref()
+            txid_seed(&txid).as_ref()
         ],
         bump = request.bump,
     )]
     pub request: Account<'info, Request>, 
 
-    pub admin: Signer<'info>,
-    pub factory_program: AccountInfo<'info>,
+    pub custodian: Signer<'info>,
 
-    pub token_program: Program<'info, Token>
+    /// Registry entry of the confirming custodian
+    #[account(
+        seeds = [
+            b"custodian".as_ref(),
+            controller_state.members.as_ref(),
+            custodian.key().as_ref()
+        ],
+        seeds::program = members::ID,
+        bump = custodian_state.bump,
+        constraint = custodian_state.active && custodian_state.allowed_assets.contains(&controller_state.token_mint) @ Errors::CustodianNotAllowed
+    )]
+    pub custodian_state: Account<'info, Custodian>,
+
+    /// CHECK: Factory authority PDA, registered as Controller.factory
+    #[account(
+        seeds = [
+            b"factory_authority".as_ref(),
+            factory_state.key().as_ref()
+        ],
+        bump = factory_state.authority_bump,
+    )]
+    pub factory_authority: UncheckedAccount<'info>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+    pub associated_token_program: Option<Program<'info, AssociatedToken>>,
+    pub system_program: Option<Program<'info, System>>,
 }
 
 #[derive(Accounts)]
@@ -408,10 +795,11 @@ pub struct RejectMintRequest<'info> {
     pub factory_state: Account<'info, FactoryState>,
 
     #[account(
+        mut,
         seeds = [
             b"mint_request".as_ref(),
             factory_state.key().as_ref(),
-            txid.as_ref()
+            txid_seed(&txid).as_ref()
         ],
         bump = request.bump,
     )]
@@ -421,20 +809,64 @@ pub struct RejectMintRequest<'info> {
     pub admin: Signer<'info>,    
 }
 
+#[derive(Accounts)]
+#[instruction(txid: String)]
+pub struct BlockMintRequest<'info> {
+    #[account(
+        has_one = controller_state,
+        constraint = signer.key() == factory_state.admin || signer.key() == controller_state.guardian @ Errors::SenderNotAuthorized
+    )]
+    pub factory_state: Account<'info, FactoryState>,
+
+    pub controller_state: Account<'info, Controller>,
+
+    #[account(
+        mut,
+        seeds = [
+            b"mint_request".as_ref(),
+            factory_state.key().as_ref(),
+            txid_seed(&txid).as_ref()
+        ],
+        bump = request.bump,
+    )]
+    pub request: Account<'info, Request>,
+
+    pub signer: Signer<'info>,
+}
+
 #[derive(Accounts)]
 pub struct AddBurnRequest<'info> {
     #[account(
         mut,
+        has_one = controller_state,
+        has_one = controller_program
     )]
     pub factory_state: Account<'info, FactoryState>,
 
+    #[account(
+        mut,
+        constraint = !controller_state.paused @ Errors::Paused,
+        constraint = controller_state.factory == factory_authority.key()
+    )]
+    pub controller_state: Account<'info, Controller>,
+    pub controller_program: Program<'info, controller::program::Controller>,
+
     pub merchant_state: Account<'info, Merchant>,
-    pub members: UncheckedAccount<'info>,
+
+    #[account(address = controller_state.members)]
     pub member_state: Account<'info, Members>,
 
-    pub token_mint: Account<'info, Mint>,
-    #[account(mut)]
-    pub token_account: Account<'info, TokenAccount>,
+    #[account(
+        mut,
+        constraint = token_mint.key() == controller_state.token_mint
+    )]
+    pub token_mint: InterfaceAccount<'info, Mint>,
+    #[account(
+        mut,
+        constraint = token_account.mint == token_mint.key() @ Errors::InvalidTokenAccount,
+        constraint = token_account.owner == merchant.key() @ Errors::InvalidTokenAccount
+    )]
+    pub token_account: InterfaceAccount<'info, TokenAccount>,
 
     #[account(
         init,
@@ -445,7 +877,7 @@ pub struct AddBurnRequest<'info> {
         ],
         bump,
         payer = merchant,
-        space = 1000
+        space = 8 + Request::INIT_SPACE
     )]
     pub request: Account<'info, Request>,
 
@@ -462,19 +894,32 @@ pub struct AddBurnRequest<'info> {
     #[account(mut)]
     pub merchant: Signer<'info>,    
 
+    /// CHECK: Factory authority PDA, registered as Controller.factory
+    #[account(
+        seeds = [
+            b"factory_authority".as_ref(),
+            factory_state.key().as_ref()
+        ],
+        bump = factory_state.authority_bump,
+    )]
+    pub factory_authority: UncheckedAccount<'info>,
+
     pub system_program: Program<'info, System>,
-    pub token_program: Program<'info, Token>
+    pub token_program: Interface<'info, TokenInterface>
 }
 
 #[derive(Accounts)]
-#[instruction(nonce: u8)]
+#[instruction(nonce: u128)]
 pub struct ConfirmBurnRequest<'info> {
     #[account(
-        has_one = admin
+        has_one = controller_state
     )]
     pub factory_state: Account<'info, FactoryState>,
 
+    pub controller_state: Account<'info, Controller>,
+
     #[account(
+        mut,
         seeds = [
             b"burn_request".as_ref(),
             factory_state.key().as_ref(),
@@ -485,11 +930,102 @@ pub struct ConfirmBurnRequest<'info> {
     pub request: Account<'info, Request>,
 
     #[account(mut)]
-    pub admin: Signer<'info>,    
+    pub custodian: Signer<'info>,
+
+    /// Registry entry of the confirming custodian
+    #[account(
+        seeds = [
+            b"custodian".as_ref(),
+            controller_state.members.as_ref(),
+            custodian.key().as_ref()
+        ],
+        seeds::program = members::ID,
+        bump = custodian_state.bump,
+        constraint = custodian_state.active && custodian_state.allowed_assets.contains(&controller_state.token_mint) @ Errors::CustodianNotAllowed
+    )]
+    pub custodian_state: Account<'info, Custodian>,
+}
+
+/// FactoryState layout from before versioning
+#[derive(AnchorDeserialize)]
+struct FactoryStateV0 {
+    admin: Pubkey,
+    controller_state: Pubkey,
+    controller_program: Pubkey,
+    mint_request_count: u128,
+    burn_request_count: u128,
+}
+
+impl FactoryStateV0 {
+    fn upgrade(self, factory_state: Pubkey) -> FactoryState {
+        let bump = |seeds: &[&[u8]]| Pubkey::find_program_address(seeds, &crate::ID).1;
+
+        FactoryState {
+            version: CURRENT_VERSION,
+            admin: self.admin,
+            controller_state: self.controller_state,
+            controller_program: self.controller_program,
+            mint_request_count: self.mint_request_count,
+            burn_request_count: self.burn_request_count,
+            authority_bump: bump(&[b"factory_authority".as_ref(), factory_state.as_ref()]),
+            bump: bump(&[b"factory".as_ref(), self.controller_state.as_ref()]),
+        }
+    }
+}
+
+/// DepositAddress layout from before versioning
+#[derive(AnchorDeserialize)]
+struct DepositAddressV0 {
+    address: String,
+    bump: u8,
+}
+
+impl DepositAddressV0 {
+    fn upgrade(self) -> DepositAddress {
+        DepositAddress {
+            version: CURRENT_VERSION,
+            address: self.address,
+            bump: self.bump,
+        }
+    }
+}
+
+/// Request layout from before versioning
+#[derive(AnchorDeserialize)]
+struct RequestV0 {
+    requester: Pubkey,
+    amount: u64,
+    deposit_address: String,
+    txid: String,
+    nonce: u128,
+    timestamp: u64,
+    status: u8,
+    bump: u8,
+}
+
+impl RequestV0 {
+    fn upgrade(self) -> Request {
+        Request {
+            version: CURRENT_VERSION,
+            requester: self.requester,
+            amount: self.amount,
+            deposit_address: self.deposit_address,
+            txid: self.txid,
+            nonce: self.nonce,
+            timestamp: self.timestamp,
+            status: self.status,
+            bump: self.bump,
+            custodian: Pubkey::default(),
+        }
+    }
 }
 
 #[account]
+#[derive(InitSpace)]
 pub struct FactoryState {
+    /// Layout Version
+    pub version: u8,
+
     /// Admin - Small DAO
     pub admin: Pubkey,
 
@@ -502,17 +1038,32 @@ pub struct FactoryState {
 
     /// Burn Request Count
     pub burn_request_count: u128,
+
+    /// Factory Authority PDA Bump
+    pub authority_bump: u8,
+
+    /// Seed Bump
+    pub bump: u8,
 }
 
 #[account]
+#[derive(InitSpace)]
 pub struct DepositAddress {
+    /// Layout Version
+    pub version: u8,
+
+    #[max_len(90)]
     pub address: String,
 
     pub bump: u8
 }
 
 #[account]
+#[derive(InitSpace)]
 pub struct Request {
+    /// Layout Version
+    pub version: u8,
+
     /// Sender of the request
     pub requester: Pubkey,
 
@@ -520,9 +1071,11 @@ pub struct Request {
     pub amount: u64,
 
     /// custodian's asset address in mint, merchant's asset address in burn.
+    #[max_len(90)]
     pub deposit_address: String,
     
     /// asset txid for sending/redeeming asset in the mint/burn process.
+    #[max_len(64)]
     pub txid: String,
 
     /// serial number allocated for each request.
@@ -534,7 +1087,119 @@ pub struct Request {
     // Status of the request
     pub status: u8,
 
-    pub bump: u8
+    pub bump: u8,
+
+    /// Custodian that confirmed the request
+    pub custodian: Pubkey,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RequestKind {
+    Mint,
+    Burn,
+}
+
+/// Return data of get_request, fields are only ever appended
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug)]
+pub struct RequestInfo {
+    pub requester: Pubkey,
+    pub amount: u64,
+    pub deposit_address: String,
+    pub txid: String,
+    pub nonce: u128,
+    pub timestamp: u64,
+    pub status: u8,
+    pub custodian: Pubkey,
+}
+
+#[event]
+pub struct FactoryInitialized {
+    pub factory_state: Pubkey,
+    pub controller_state: Pubkey,
+    pub admin: Pubkey,
+}
+
+#[event]
+pub struct DepositAddressSet {
+    pub factory_state: Pubkey,
+    pub merchant: Pubkey,
+    /// true for the custodian's address for this merchant, false for the merchant's own
+    pub custodian: bool,
+    pub address: String,
+}
+
+#[event]
+pub struct MintRequestAdded {
+    pub factory_state: Pubkey,
+    pub request: Pubkey,
+    pub requester: Pubkey,
+    pub nonce: u128,
+    pub amount: u64,
+    pub txid: String,
+    pub deposit_address: String,
+    pub timestamp: u64,
+}
+
+#[event]
+pub struct MintRequestCancelled {
+    pub factory_state: Pubkey,
+    pub request: Pubkey,
+    pub nonce: u128,
+    pub txid: String,
+}
+
+#[event]
+pub struct MintRequestConfirmed {
+    pub factory_state: Pubkey,
+    pub request: Pubkey,
+    pub nonce: u128,
+    pub amount: u64,
+    pub txid: String,
+    pub custodian: Pubkey,
+}
+
+#[event]
+pub struct MintRequestRejected {
+    pub factory_state: Pubkey,
+    pub request: Pubkey,
+    pub nonce: u128,
+    pub txid: String,
+}
+
+#[event]
+pub struct MintRequestBlocked {
+    pub factory_state: Pubkey,
+    pub request: Pubkey,
+    pub nonce: u128,
+    pub txid: String,
+    pub by: Pubkey,
+}
+
+#[event]
+pub struct BurnRequestAdded {
+    pub factory_state: Pubkey,
+    pub request: Pubkey,
+    pub requester: Pubkey,
+    pub nonce: u128,
+    pub amount: u64,
+    pub deposit_address: String,
+    pub timestamp: u64,
+}
+
+#[event]
+pub struct BurnRequestConfirmed {
+    pub factory_state: Pubkey,
+    pub request: Pubkey,
+    pub nonce: u128,
+    pub txid: String,
+    pub custodian: Pubkey,
+}
+
+#[event]
+pub struct Migrated {
+    pub account: Pubkey,
+    pub from_version: u8,
+    pub to_version: u8,
 }
 
 #[error_code]
@@ -545,6 +1210,24 @@ pub enum Errors {
     InvalidDepositAddress,
     #[msg("invalid asset txid")]
     InvalidTxid,
+    #[msg("controller is paused")]
+    Paused,
+    #[msg("account can't be migrated by this program")]
+    InvalidMigration,
+    #[msg("account is already at the current version")]
+    AlreadyMigrated,
+    #[msg("merchant is frozen")]
+    MerchantFrozen,
+    #[msg("merchant has been removed")]
+    MerchantInactive,
+    #[msg("request is not pending")]
+    RequestNotPending,
+    #[msg("request doesn't match the given kind and nonce")]
+    InvalidRequest,
+    #[msg("custodian is inactive or not allowed for this asset")]
+    CustodianNotAllowed,
+    #[msg("token account has the wrong mint or owner")]
+    InvalidTokenAccount,
 
 }
 
@@ -552,4 +1235,5 @@ pub enum Errors {
 // PENDING - 0
 // CANCELLED - 1
 // APPROVED - 2
-// REJECTED - 3
\ No newline at end of file
+// REJECTED - 3
+// BLOCKED - 4
\ No newline at end of file
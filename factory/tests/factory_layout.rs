@@ -0,0 +1,44 @@
+use anchor_lang::prelude::*;
+use anchor_lang::Discriminator;
+use factory::FactoryState;
+
+fn factory_state(controller_state: Pubkey) -> FactoryState {
+    FactoryState {
+        version: factory::CURRENT_VERSION,
+        admin: Pubkey::new_unique(),
+        controller_state,
+        controller_program: controller::ID,
+        mint_request_count: u128::MAX,
+        burn_request_count: u128::MAX,
+        authority_bump: 254,
+        bump: 253,
+    }
+}
+
+#[test]
+fn controller_reads_the_factory_discriminator() {
+    assert_eq!(controller::FACTORY_STATE_DISCRIMINATOR, FactoryState::DISCRIMINATOR);
+}
+
+#[test]
+fn controller_reads_controller_state_at_its_offset() {
+    let controller_state = Pubkey::new_unique();
+
+    let mut data = Vec::new();
+    factory_state(controller_state).try_serialize(&mut data).unwrap();
+
+    assert_eq!(data[..8], controller::FACTORY_STATE_DISCRIMINATOR);
+    assert_eq!(data[controller::FACTORY_STATE_CONTROLLER_RANGE], controller_state.to_bytes());
+}
+
+#[test]
+fn controller_pins_the_factory_program() {
+    assert_eq!(controller::factory_program::ID, factory::ID);
+}
+
+#[test]
+fn programs_have_distinct_ids() {
+    assert_ne!(factory::ID, controller::ID);
+    assert_ne!(factory::ID, members::ID);
+    assert_ne!(controller::ID, members::ID);
+}
@@ -0,0 +1,788 @@
+use anchor_lang::prelude::*;
+use anchor_lang::solana_program::entrypoint::ProgramResult;
+use anchor_lang::solana_program::instruction::Instruction;
+use anchor_lang::solana_program::program_option::COption;
+use anchor_lang::solana_program::system_program;
+use anchor_lang::{InstructionData, ToAccountMetas};
+use test_runtime::{Entry, Runtime};
+use controller::Controller;
+use factory::{DepositAddress, Errors, FactoryState, Request};
+use members::{Custodian, Members, Merchant};
+use spl_token::state::{Account as TokenAccount, AccountState, Mint};
+
+const SUPPLY: u64 = 1_000_000;
+const CUSTODIAN_ADDRESS: &str = "bc1qcustodian";
+const MERCHANT_ADDRESS: &str = "bc1qmerchant";
+const BTC_TXID: &str = "4a5e1e4baab89f3a32518a88c31bc87f618f76673e2cc77ab2127b7afdeda33b";
+
+fn pda(seeds: &[&[u8]], program_id: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(seeds, program_id)
+}
+
+fn runtime() -> Runtime {
+    Runtime::new(&[
+        (factory::ID, factory::entry as Entry),
+        (controller::ID, controller::entry as Entry),
+        (members::ID, members::entry as Entry),
+    ])
+}
+
+fn err(error: impl Into<anchor_lang::error::Error>) -> ProgramError {
+    error.into().into()
+}
+
+struct Fixture {
+    runtime: Runtime,
+    admin: Pubkey,
+    custodian: Pubkey,
+    member_state: Pubkey,
+    token_mint: Pubkey,
+    controller_state: Pubkey,
+    mint_authority: Pubkey,
+    factory_state: Pubkey,
+    factory_authority: Pubkey,
+    merchant: Pubkey,
+    merchant_tokens: Pubkey,
+}
+
+impl Fixture {
+    fn new() -> Self {
+        let mut runtime = runtime();
+
+        let admin = Pubkey::new_unique();
+        let custodian = Pubkey::new_unique();
+        let token_mint = Pubkey::new_unique();
+        runtime.fund(admin);
+        runtime.fund(custodian);
+
+        let (member_state, member_bump) = pda(&[b"members"], &members::ID);
+        runtime.set_state(member_state, members::ID, 8 + Members::INIT_SPACE, &Members {
+            version: members::CURRENT_VERSION,
+            admin,
+            custodian,
+            bump: member_bump,
+            controller: Pubkey::default(),
+            pending_admin: Pubkey::default(),
+            merchant_count: 0,
+        });
+
+        let (controller_state, controller_bump) = pda(&[b"controller", token_mint.as_ref()], &controller::ID);
+        let (mint_authority, mint_authority_bump) = pda(&[b"mint_authority", controller_state.as_ref()], &controller::ID);
+        let (factory_state, factory_bump) = pda(&[b"factory", controller_state.as_ref()], &factory::ID);
+        let (factory_authority, authority_bump) = pda(&[b"factory_authority", factory_state.as_ref()], &factory::ID);
+
+        runtime.set_state(controller_state, controller::ID, 8 + Controller::INIT_SPACE, &Controller {
+            version: controller::CURRENT_VERSION,
+            owner: admin,
+            pending_owner: Pubkey::default(),
+            token_mint,
+            members: member_state,
+            factory: factory_authority,
+            paused: false,
+            pauser: Pubkey::default(),
+            mint_authority_bump,
+            max_supply: 0,
+            epoch_mint_limit: 0,
+            epoch_duration: 86400,
+            epoch_start: 0,
+            epoch_minted: 0,
+            compliance: Pubkey::default(),
+            freeze_authority_bump: 0,
+            enforce_reserves: false,
+            timelock_delay: controller::DEFAULT_TIMELOCK_DELAY,
+            bump: controller_bump,
+            total_minted: 0,
+            total_burned: 0,
+            mint_count: 0,
+            burn_count: 0,
+            guardian: Pubkey::default(),
+            prev_epoch_minted: 0,
+        });
+
+        runtime.set_state(factory_state, factory::ID, 8 + FactoryState::INIT_SPACE, &FactoryState {
+            version: factory::CURRENT_VERSION,
+            admin,
+            controller_state,
+            controller_program: controller::ID,
+            mint_request_count: 0,
+            burn_request_count: 0,
+            authority_bump,
+            bump: factory_bump,
+        });
+
+        runtime.set_pack(token_mint, spl_token::ID, Mint {
+            mint_authority: COption::Some(mint_authority),
+            supply: SUPPLY,
+            decimals: 8,
+            is_initialized: true,
+            freeze_authority: COption::None,
+        });
+
+        let mut fixture = Fixture {
+            runtime,
+            admin,
+            custodian,
+            member_state,
+            token_mint,
+            controller_state,
+            mint_authority,
+            factory_state,
+            factory_authority,
+            merchant: Pubkey::default(),
+            merchant_tokens: Pubkey::default(),
+        };
+
+        fixture.set_custodian_state(custodian, vec![token_mint]);
+        (fixture.merchant, fixture.merchant_tokens) = fixture.add_merchant();
+        fixture.set_deposit_address(b"custodian_deposit", CUSTODIAN_ADDRESS);
+        fixture.set_deposit_address(b"merchant_deposit", MERCHANT_ADDRESS);
+
+        fixture
+    }
+
+    /// Registers a merchant holding the whole supply
+    fn add_merchant(&mut self) -> (Pubkey, Pubkey) {
+        let merchant = Pubkey::new_unique();
+        let merchant_tokens = Pubkey::new_unique();
+        self.runtime.fund(merchant);
+
+        let (merchant_state, bump) = self.merchant_state(merchant);
+        self.runtime.set_state(merchant_state, members::ID, 8 + Merchant::INIT_SPACE, &Merchant {
+            version: members::CURRENT_VERSION,
+            merchant,
+            active: true,
+            bump,
+            frozen: false,
+            index: 0,
+            indexed: true,
+            display_name: String::new(),
+            jurisdiction: String::new(),
+            kyc_provider: String::new(),
+            attestation_hash: [0; 32],
+            onboarded_at: 0,
+            kyc_expires_at: 0,
+        });
+
+        self.runtime.set_pack(merchant_tokens, spl_token::ID, TokenAccount {
+            mint: self.token_mint,
+            owner: merchant,
+            amount: SUPPLY,
+            delegate: COption::None,
+            state: AccountState::Initialized,
+            is_native: COption::None,
+            delegated_amount: 0,
+            close_authority: COption::None,
+        });
+
+        (merchant, merchant_tokens)
+    }
+
+    fn merchant_state(&self, merchant: Pubkey) -> (Pubkey, u8) {
+        pda(&[b"merchant", self.member_state.as_ref(), merchant.as_ref()], &members::ID)
+    }
+
+    fn update_merchant(&mut self, update: impl FnOnce(&mut Merchant)) {
+        let (merchant_state, _) = self.merchant_state(self.merchant);
+        let mut state: Merchant = self.runtime.state(&merchant_state);
+        update(&mut state);
+        self.runtime.set_state(merchant_state, members::ID, 8 + Merchant::INIT_SPACE, &state);
+    }
+
+    fn set_custodian_state(&mut self, custodian: Pubkey, allowed_assets: Vec<Pubkey>) {
+        let (custodian_state, bump) = pda(&[b"custodian", self.member_state.as_ref(), custodian.as_ref()], &members::ID);
+        self.runtime.set_state(custodian_state, members::ID, 8 + Custodian::INIT_SPACE, &Custodian {
+            version: members::CURRENT_VERSION,
+            custodian,
+            active: true,
+            label: String::new(),
+            allowed_assets,
+            bump,
+        });
+    }
+
+    fn deposit_address(&self, seed: &[u8]) -> (Pubkey, u8) {
+        pda(&[seed, self.factory_state.as_ref(), self.merchant.as_ref()], &factory::ID)
+    }
+
+    fn set_deposit_address(&mut self, seed: &[u8], address: &str) {
+        let (deposit_address, bump) = self.deposit_address(seed);
+        self.runtime.set_state(deposit_address, factory::ID, 8 + DepositAddress::INIT_SPACE, &DepositAddress {
+            version: factory::CURRENT_VERSION,
+            address: address.to_string(),
+            bump,
+        });
+    }
+
+    fn mint_request(&self, txid: &str) -> Pubkey {
+        pda(&[b"mint_request", self.factory_state.as_ref(), &factory::txid_seed(txid)], &factory::ID).0
+    }
+
+    fn burn_request(&self, nonce: u128) -> Pubkey {
+        pda(&[b"burn_request", self.factory_state.as_ref(), nonce.to_string().as_ref()], &factory::ID).0
+    }
+
+    fn process(&mut self, accounts: impl ToAccountMetas, data: impl InstructionData) -> ProgramResult {
+        self.runtime.process(Instruction {
+            program_id: factory::ID,
+            accounts: accounts.to_account_metas(None),
+            data: data.data(),
+        })
+    }
+
+    fn set_custodian_deposit_address(&mut self, signer: Pubkey, deposit_address: &str) -> ProgramResult {
+        let custodian_state = pda(&[b"custodian", self.member_state.as_ref(), signer.as_ref()], &members::ID).0;
+
+        self.process(
+            factory::accounts::SetCustodianDepositAddress {
+                factory_state: self.factory_state,
+                controller_state: self.controller_state,
+                merchant: self.merchant,
+                merchant_state: self.merchant_state(self.merchant).0,
+                member_state: self.member_state,
+                custodian_deposit_address: self.deposit_address(b"custodian_deposit").0,
+                signer,
+                custodian_state,
+                system_program: system_program::ID,
+            },
+            factory::instruction::SetCustodianDepositAddress { deposit_address: deposit_address.to_string() },
+        )
+    }
+
+    fn set_merchant_deposit_address(&mut self, deposit_address: &str) -> ProgramResult {
+        self.process(
+            factory::accounts::SetMerchantDepositAddress {
+                factory_state: self.factory_state,
+                controller_state: self.controller_state,
+                merchant_state: self.merchant_state(self.merchant).0,
+                member_state: self.member_state,
+                merchant_deposit_address: self.deposit_address(b"merchant_deposit").0,
+                merchant: self.merchant,
+                system_program: system_program::ID,
+            },
+            factory::instruction::SetMerchantDepositAddress { deposit_address: deposit_address.to_string() },
+        )
+    }
+
+    fn add_mint_request(&mut self, txid: &str, deposit_address: &str, amount: u64) -> ProgramResult {
+        self.process(
+            factory::accounts::AddMintRequest {
+                factory_state: self.factory_state,
+                controller_state: self.controller_state,
+                merchant_state: self.merchant_state(self.merchant).0,
+                member_state: self.member_state,
+                request: self.mint_request(txid),
+                custodian_deposit_address: self.deposit_address(b"custodian_deposit").0,
+                merchant: self.merchant,
+                system_program: system_program::ID,
+            },
+            factory::instruction::AddMintRequest {
+                txid: txid.to_string(),
+                deposit_address: deposit_address.to_string(),
+                amount,
+            },
+        )
+    }
+
+    fn cancel_mint_request(&mut self, merchant: Pubkey, txid: &str) -> ProgramResult {
+        self.process(
+            factory::accounts::CancelMintRequest {
+                factory_state: self.factory_state,
+                controller_state: self.controller_state,
+                merchant_state: self.merchant_state(merchant).0,
+                member_state: self.member_state,
+                request: self.mint_request(txid),
+                merchant,
+            },
+            factory::instruction::CancelMintRequest { txid: txid.to_string() },
+        )
+    }
+
+    fn confirm_mint_request(&mut self, txid: &str) -> ProgramResult {
+        self.process(
+            factory::accounts::ConfirmMintRequest {
+                factory_state: self.factory_state,
+                controller_state: self.controller_state,
+                controller_program: controller::ID,
+                token_mint: self.token_mint,
+                token_account: self.merchant_tokens,
+                requester: self.merchant,
+                requester_state: self.merchant_state(self.merchant).0,
+                payer: None,
+                mint_authority: self.mint_authority,
+                reserves: None,
+                request: self.mint_request(txid),
+                custodian: self.custodian,
+                custodian_state: pda(&[b"custodian", self.member_state.as_ref(), self.custodian.as_ref()], &members::ID).0,
+                factory_authority: self.factory_authority,
+                token_program: spl_token::ID,
+                associated_token_program: None,
+                system_program: None,
+            },
+            factory::instruction::ConfirmMintRequest { txid: txid.to_string() },
+        )
+    }
+
+    fn reject_mint_request(&mut self, txid: &str) -> ProgramResult {
+        self.process(
+            factory::accounts::RejectMintRequest {
+                factory_state: self.factory_state,
+                request: self.mint_request(txid),
+                admin: self.admin,
+            },
+            factory::instruction::RejectMintRequest { txid: txid.to_string() },
+        )
+    }
+
+    fn add_burn_request(&mut self, amount: u64) -> ProgramResult {
+        let state: FactoryState = self.runtime.state(&self.factory_state);
+
+        self.process(
+            factory::accounts::AddBurnRequest {
+                factory_state: self.factory_state,
+                controller_state: self.controller_state,
+                controller_program: controller::ID,
+                merchant_state: self.merchant_state(self.merchant).0,
+                member_state: self.member_state,
+                token_mint: self.token_mint,
+                token_account: self.merchant_tokens,
+                request: self.burn_request(state.burn_request_count + 1),
+                merchant_deposit_address: self.deposit_address(b"merchant_deposit").0,
+                merchant: self.merchant,
+                factory_authority: self.factory_authority,
+                system_program: system_program::ID,
+                token_program: spl_token::ID,
+            },
+            factory::instruction::AddBurnRequest { amount },
+        )
+    }
+
+    /// Stores a copy of the fixture's controller at `key`, wired to `members` and guarded by `guardian`
+    fn set_controller_state(&mut self, key: Pubkey, members: Pubkey, guardian: Pubkey) {
+        let mut controller_state: Controller = self.runtime.state(&self.controller_state);
+        controller_state.members = members;
+        controller_state.guardian = guardian;
+        self.runtime.set_state(key, controller::ID, 8 + Controller::INIT_SPACE, &controller_state);
+    }
+
+    fn set_controller(&mut self, controller_state: Pubkey) -> ProgramResult {
+        let accounts = members::accounts::SetController {
+            member_state: self.member_state,
+            admin: self.admin,
+            controller_state,
+        };
+
+        self.runtime.process(Instruction {
+            program_id: members::ID,
+            accounts: accounts.to_account_metas(None),
+            data: members::instruction::SetController {}.data(),
+        })
+    }
+
+    fn freeze_merchant(&mut self, signer: Pubkey, controller_state: Option<Pubkey>) -> ProgramResult {
+        let accounts = members::accounts::FreezeMerchant {
+            member_state: self.member_state,
+            signer,
+            controller_state,
+            merchant: self.merchant,
+            merchant_state: self.merchant_state(self.merchant).0,
+        };
+
+        self.runtime.process(Instruction {
+            program_id: members::ID,
+            accounts: accounts.to_account_metas(None),
+            data: members::instruction::FreezeMerchant {}.data(),
+        })
+    }
+
+    fn confirm_burn_request(&mut self, nonce: u128, txid: &str) -> ProgramResult {
+        self.process(
+            factory::accounts::ConfirmBurnRequest {
+                factory_state: self.factory_state,
+                controller_state: self.controller_state,
+                request: self.burn_request(nonce),
+                custodian: self.custodian,
+                custodian_state: pda(&[b"custodian", self.member_state.as_ref(), self.custodian.as_ref()], &members::ID).0,
+            },
+            factory::instruction::ConfirmBurnRequest { nonce, txid: txid.to_string() },
+        )
+    }
+}
+
+#[test]
+fn registered_custodian_sets_deposit_address() {
+    let mut fixture = Fixture::new();
+    let custodian = fixture.custodian;
+
+    fixture.set_custodian_deposit_address(custodian, "bc1qrotated").unwrap();
+
+    let address: DepositAddress = fixture.runtime.state(&fixture.deposit_address(b"custodian_deposit").0);
+    assert_eq!(address.address, "bc1qrotated");
+}
+
+#[test]
+fn unregistered_signer_cannot_set_custodian_deposit_address() {
+    let mut fixture = Fixture::new();
+    let merchant = fixture.merchant;
+
+    assert_eq!(
+        fixture.set_custodian_deposit_address(merchant, "bc1qattacker").unwrap_err(),
+        err(ErrorCode::AccountNotInitialized)
+    );
+}
+
+#[test]
+fn custodian_must_be_allowed_the_asset_to_set_deposit_address() {
+    let mut fixture = Fixture::new();
+    let custodian = fixture.custodian;
+    fixture.set_custodian_state(custodian, vec![Pubkey::new_unique()]);
+
+    assert_eq!(
+        fixture.set_custodian_deposit_address(custodian, "bc1qrotated").unwrap_err(),
+        err(Errors::CustodianNotAllowed)
+    );
+}
+
+#[test]
+fn merchant_sets_deposit_address() {
+    let mut fixture = Fixture::new();
+
+    fixture.set_merchant_deposit_address("bc1qrotated").unwrap();
+
+    let address: DepositAddress = fixture.runtime.state(&fixture.deposit_address(b"merchant_deposit").0);
+    assert_eq!(address.address, "bc1qrotated");
+}
+
+#[test]
+fn removed_merchant_cannot_set_deposit_address() {
+    let mut fixture = Fixture::new();
+    fixture.update_merchant(|merchant| merchant.active = false);
+
+    assert_eq!(
+        fixture.set_merchant_deposit_address("bc1qrotated").unwrap_err(),
+        err(Errors::MerchantInactive)
+    );
+}
+
+#[test]
+fn add_mint_request_files_a_pending_request() {
+    let mut fixture = Fixture::new();
+
+    fixture.add_mint_request("txid", CUSTODIAN_ADDRESS, 500).unwrap();
+
+    let request: Request = fixture.runtime.state(&fixture.mint_request("txid"));
+    assert_eq!(request.requester, fixture.merchant);
+    assert_eq!(request.amount, 500);
+    assert_eq!(request.nonce, 1);
+    assert_eq!(request.status, 0);
+
+    let factory_state: FactoryState = fixture.runtime.state(&fixture.factory_state);
+    assert_eq!(factory_state.mint_request_count, 1);
+}
+
+#[test]
+fn mint_request_for_a_full_length_btc_txid_is_confirmed() {
+    let mut fixture = Fixture::new();
+
+    fixture.add_mint_request(BTC_TXID, CUSTODIAN_ADDRESS, 500).unwrap();
+    fixture.confirm_mint_request(BTC_TXID).unwrap();
+
+    let request: Request = fixture.runtime.state(&fixture.mint_request(BTC_TXID));
+    assert_eq!(request.txid, BTC_TXID);
+    assert_eq!(request.status, 2);
+
+    let too_long = format!("{BTC_TXID}0");
+    assert_eq!(
+        fixture.add_mint_request(&too_long, CUSTODIAN_ADDRESS, 500).unwrap_err(),
+        err(Errors::InvalidTxid)
+    );
+}
+
+#[test]
+fn add_mint_request_checks_the_custodian_deposit_address() {
+    let mut fixture = Fixture::new();
+
+    assert_eq!(
+        fixture.add_mint_request("txid", "bc1qelsewhere", 500).unwrap_err(),
+        err(Errors::InvalidDepositAddress)
+    );
+    assert!(fixture.runtime.account(&fixture.mint_request("txid")).is_none());
+}
+
+#[test]
+fn merchant_of_another_registry_cannot_add_mint_request() {
+    let mut fixture = Fixture::new();
+
+    // A registry the attacker administers, with the merchant approved in it
+    let rogue = Pubkey::new_unique();
+    let mut member_state: Members = fixture.runtime.state(&fixture.member_state);
+    member_state.admin = fixture.merchant;
+    fixture.runtime.set_state(rogue, members::ID, 8 + Members::INIT_SPACE, &member_state);
+
+    let (merchant_state, _) = fixture.merchant_state(fixture.merchant);
+    let (rogue_merchant_state, bump) = pda(&[b"merchant", rogue.as_ref(), fixture.merchant.as_ref()], &members::ID);
+    let mut state: Merchant = fixture.runtime.state(&merchant_state);
+    state.bump = bump;
+    fixture.runtime.set_state(rogue_merchant_state, members::ID, 8 + Merchant::INIT_SPACE, &state);
+    fixture.update_merchant(|merchant| merchant.active = false);
+
+    let result = fixture.process(
+        factory::accounts::AddMintRequest {
+            factory_state: fixture.factory_state,
+            controller_state: fixture.controller_state,
+            merchant_state: rogue_merchant_state,
+            member_state: rogue,
+            request: fixture.mint_request("txid"),
+            custodian_deposit_address: fixture.deposit_address(b"custodian_deposit").0,
+            merchant: fixture.merchant,
+            system_program: system_program::ID,
+        },
+        factory::instruction::AddMintRequest {
+            txid: "txid".to_string(),
+            deposit_address: CUSTODIAN_ADDRESS.to_string(),
+            amount: 500,
+        },
+    );
+
+    assert_eq!(result.unwrap_err(), err(ErrorCode::ConstraintAddress));
+}
+
+#[test]
+fn frozen_merchant_cannot_add_mint_request() {
+    let mut fixture = Fixture::new();
+    fixture.update_merchant(|merchant| merchant.frozen = true);
+
+    assert_eq!(
+        fixture.add_mint_request("txid", CUSTODIAN_ADDRESS, 500).unwrap_err(),
+        err(Errors::MerchantFrozen)
+    );
+}
+
+#[test]
+fn merchant_cancels_its_mint_request() {
+    let mut fixture = Fixture::new();
+    let merchant = fixture.merchant;
+    fixture.add_mint_request("txid", CUSTODIAN_ADDRESS, 500).unwrap();
+
+    fixture.cancel_mint_request(merchant, "txid").unwrap();
+
+    let request: Request = fixture.runtime.state(&fixture.mint_request("txid"));
+    assert_eq!(request.status, 1);
+
+    assert_eq!(
+        fixture.cancel_mint_request(merchant, "txid").unwrap_err(),
+        err(Errors::RequestNotPending)
+    );
+}
+
+#[test]
+fn merchant_cannot_cancel_another_merchants_request() {
+    let mut fixture = Fixture::new();
+    fixture.add_mint_request("txid", CUSTODIAN_ADDRESS, 500).unwrap();
+    let (other, _) = fixture.add_merchant();
+
+    assert_eq!(
+        fixture.cancel_mint_request(other, "txid").unwrap_err(),
+        err(Errors::SenderNotAuthorized)
+    );
+}
+
+#[test]
+fn confirm_mint_request_mints_to_the_requester() {
+    let mut fixture = Fixture::new();
+    fixture.add_mint_request("txid", CUSTODIAN_ADDRESS, 500).unwrap();
+
+    fixture.confirm_mint_request("txid").unwrap();
+
+    let tokens: TokenAccount = fixture.runtime.unpack(&fixture.merchant_tokens);
+    assert_eq!(tokens.amount, SUPPLY + 500);
+
+    let request: Request = fixture.runtime.state(&fixture.mint_request("txid"));
+    assert_eq!(request.status, 2);
+    assert_eq!(request.custodian, fixture.custodian);
+
+    let controller_state: Controller = fixture.runtime.state(&fixture.controller_state);
+    assert_eq!(controller_state.total_minted, 500);
+    assert_eq!(controller_state.mint_count, 1);
+}
+
+#[test]
+fn removed_merchants_request_cannot_be_confirmed() {
+    let mut fixture = Fixture::new();
+    fixture.add_mint_request("txid", CUSTODIAN_ADDRESS, 500).unwrap();
+    fixture.update_merchant(|merchant| merchant.active = false);
+
+    assert_eq!(
+        fixture.confirm_mint_request("txid").unwrap_err(),
+        err(Errors::MerchantInactive)
+    );
+}
+
+#[test]
+fn cancelled_mint_request_cannot_be_confirmed() {
+    let mut fixture = Fixture::new();
+    let merchant = fixture.merchant;
+    fixture.add_mint_request("txid", CUSTODIAN_ADDRESS, 500).unwrap();
+    fixture.cancel_mint_request(merchant, "txid").unwrap();
+
+    assert_eq!(
+        fixture.confirm_mint_request("txid").unwrap_err(),
+        err(Errors::RequestNotPending)
+    );
+
+    let tokens: TokenAccount = fixture.runtime.unpack(&fixture.merchant_tokens);
+    assert_eq!(tokens.amount, SUPPLY);
+}
+
+#[test]
+fn rejected_mint_request_cannot_be_confirmed() {
+    let mut fixture = Fixture::new();
+    fixture.add_mint_request("txid", CUSTODIAN_ADDRESS, 500).unwrap();
+    fixture.reject_mint_request("txid").unwrap();
+
+    let request: Request = fixture.runtime.state(&fixture.mint_request("txid"));
+    assert_eq!(request.status, 3);
+
+    assert_eq!(
+        fixture.confirm_mint_request("txid").unwrap_err(),
+        err(Errors::RequestNotPending)
+    );
+    assert_eq!(
+        fixture.reject_mint_request("txid").unwrap_err(),
+        err(Errors::RequestNotPending)
+    );
+
+    let tokens: TokenAccount = fixture.runtime.unpack(&fixture.merchant_tokens);
+    assert_eq!(tokens.amount, SUPPLY);
+}
+
+#[test]
+fn add_burn_request_burns_through_the_controller() {
+    let mut fixture = Fixture::new();
+
+    fixture.add_burn_request(300).unwrap();
+
+    let tokens: TokenAccount = fixture.runtime.unpack(&fixture.merchant_tokens);
+    assert_eq!(tokens.amount, SUPPLY - 300);
+
+    let mint: Mint = fixture.runtime.unpack(&fixture.token_mint);
+    assert_eq!(mint.supply, SUPPLY - 300);
+
+    let controller_state: Controller = fixture.runtime.state(&fixture.controller_state);
+    assert_eq!(controller_state.total_burned, 300);
+    assert_eq!(controller_state.burn_count, 1);
+
+    let request: Request = fixture.runtime.state(&fixture.burn_request(1));
+    assert_eq!(request.requester, fixture.merchant);
+    assert_eq!(request.amount, 300);
+    assert_eq!(request.status, 0);
+}
+
+#[test]
+fn custodian_confirms_burn_request() {
+    let mut fixture = Fixture::new();
+    fixture.add_burn_request(300).unwrap();
+
+    fixture.confirm_burn_request(1, "txid").unwrap();
+
+    let request: Request = fixture.runtime.state(&fixture.burn_request(1));
+    assert_eq!(request.status, 2);
+    assert_eq!(request.txid, "txid");
+    assert_eq!(request.custodian, fixture.custodian);
+
+    assert_eq!(
+        fixture.confirm_burn_request(1, "txid").unwrap_err(),
+        err(Errors::RequestNotPending)
+    );
+}
+
+#[test]
+fn trusted_controllers_guardian_freezes_merchant() {
+    let mut fixture = Fixture::new();
+    let (controller, member_state, guardian) = (fixture.controller_state, fixture.member_state, Pubkey::new_unique());
+    fixture.set_controller_state(controller, member_state, guardian);
+
+    assert_eq!(
+        fixture.freeze_merchant(guardian, Some(controller)).unwrap_err(),
+        err(members::Errors::InvalidController)
+    );
+
+    fixture.set_controller(controller).unwrap();
+
+    assert_eq!(
+        fixture.freeze_merchant(guardian, None).unwrap_err(),
+        err(members::Errors::SenderNotAuthorized)
+    );
+    assert_eq!(
+        fixture.freeze_merchant(Pubkey::new_unique(), Some(controller)).unwrap_err(),
+        err(members::Errors::SenderNotAuthorized)
+    );
+
+    fixture.freeze_merchant(guardian, Some(controller)).unwrap();
+
+    let merchant: Merchant = fixture.runtime.state(&fixture.merchant_state(fixture.merchant).0);
+    assert!(merchant.frozen);
+}
+
+#[test]
+fn guardian_of_another_controller_on_the_registry_cannot_freeze() {
+    let mut fixture = Fixture::new();
+    let (controller, member_state) = (fixture.controller_state, fixture.member_state);
+    fixture.set_controller_state(controller, member_state, Pubkey::new_unique());
+    fixture.set_controller(controller).unwrap();
+
+    // Anyone can initialize a controller and point it at the registry
+    let (rogue, attacker) = (Pubkey::new_unique(), Pubkey::new_unique());
+    fixture.set_controller_state(rogue, member_state, attacker);
+
+    assert_eq!(
+        fixture.freeze_merchant(attacker, Some(rogue)).unwrap_err(),
+        err(members::Errors::InvalidController)
+    );
+
+    let merchant: Merchant = fixture.runtime.state(&fixture.merchant_state(fixture.merchant).0);
+    assert!(!merchant.frozen);
+}
+
+#[test]
+fn admin_only_trusts_a_controller_wired_to_the_registry() {
+    let mut fixture = Fixture::new();
+    let elsewhere = Pubkey::new_unique();
+    fixture.set_controller_state(elsewhere, Pubkey::new_unique(), Pubkey::new_unique());
+
+    assert_eq!(
+        fixture.set_controller(elsewhere).unwrap_err(),
+        err(members::Errors::InvalidController)
+    );
+
+    let controller = fixture.controller_state;
+    fixture.set_controller(controller).unwrap();
+
+    let member_state: Members = fixture.runtime.state(&fixture.member_state);
+    assert_eq!(member_state.controller, controller);
+}
+
+#[test]
+fn custodian_confirms_burn_request_past_nonce_255() {
+    let mut fixture = Fixture::new();
+    let mut factory_state: FactoryState = fixture.runtime.state(&fixture.factory_state);
+    factory_state.burn_request_count = 300;
+    fixture.runtime.set_state(fixture.factory_state, factory::ID, 8 + FactoryState::INIT_SPACE, &factory_state);
+
+    fixture.add_burn_request(300).unwrap();
+    fixture.confirm_burn_request(301, "txid").unwrap();
+
+    let request: Request = fixture.runtime.state(&fixture.burn_request(301));
+    assert_eq!(request.nonce, 301);
+    assert_eq!(request.status, 2);
+}
+
+#[test]
+fn removed_merchant_cannot_add_burn_request() {
+    let mut fixture = Fixture::new();
+    fixture.update_merchant(|merchant| merchant.active = false);
+
+    assert_eq!(
+        fixture.add_burn_request(300).unwrap_err(),
+        err(Errors::MerchantInactive)
+    );
+}
@@ -0,0 +1,61 @@
+use anchor_lang::prelude::*;
+use factory::{check_merchant_status, Errors};
+use members::Merchant;
+
+fn merchant(active: bool, frozen: bool) -> Merchant {
+    Merchant {
+        version: members::CURRENT_VERSION,
+        merchant: Pubkey::new_unique(),
+        active,
+        bump: 255,
+        frozen,
+        index: 0,
+        indexed: true,
+        display_name: String::new(),
+        jurisdiction: String::new(),
+        kyc_provider: String::new(),
+        attestation_hash: [0; 32],
+        onboarded_at: 0,
+        kyc_expires_at: 0,
+    }
+}
+
+#[test]
+fn active_merchant_is_allowed() {
+    assert!(check_merchant_status(&merchant(true, false)).is_ok());
+}
+
+#[test]
+fn removed_merchant_is_locked_out() {
+    assert_eq!(
+        check_merchant_status(&merchant(false, false)).unwrap_err(),
+        Errors::MerchantInactive.into()
+    );
+}
+
+#[test]
+fn frozen_merchant_is_locked_out() {
+    assert_eq!(
+        check_merchant_status(&merchant(true, true)).unwrap_err(),
+        Errors::MerchantFrozen.into()
+    );
+}
+
+#[test]
+fn removed_takes_precedence_over_frozen() {
+    assert_eq!(
+        check_merchant_status(&merchant(false, true)).unwrap_err(),
+        Errors::MerchantInactive.into()
+    );
+}
+
+#[test]
+fn zeroed_merchant_is_locked_out() {
+    // A default account, e.g. a merchant PDA that was never added, reads as removed
+    let merchant = Merchant::try_deserialize_unchecked(&mut &[0u8; 8 + Merchant::INIT_SPACE][..]).unwrap();
+
+    assert_eq!(
+        check_merchant_status(&merchant).unwrap_err(),
+        Errors::MerchantInactive.into()
+    );
+}
@@ -0,0 +1,70 @@
+use anchor_lang::prelude::*;
+use anchor_lang::solana_program::entrypoint::ProgramResult;
+use anchor_lang::solana_program::instruction::Instruction;
+use anchor_lang::solana_program::system_program;
+use anchor_lang::{Discriminator, InstructionData, ToAccountMetas};
+use test_runtime::{Account, Entry, Runtime};
+use factory::{Errors, FactoryState};
+
+fn runtime() -> Runtime {
+    Runtime::new(&[(factory::ID, factory::entry as Entry)])
+}
+
+fn migrate(runtime: &mut Runtime, account: Pubkey, payer: Pubkey) -> ProgramResult {
+    let accounts = factory::accounts::Migrate {
+        account,
+        payer,
+        system_program: system_program::ID,
+    };
+
+    runtime.process(Instruction {
+        program_id: factory::ID,
+        accounts: accounts.to_account_metas(None),
+        data: factory::instruction::Migrate {}.data(),
+    })
+}
+
+#[test]
+fn migrate_rewrites_an_unversioned_factory_state() {
+    let mut runtime = runtime();
+    let payer = Pubkey::new_unique();
+    runtime.fund(payer);
+
+    let (admin, controller_state) = (Pubkey::new_unique(), Pubkey::new_unique());
+    let (factory_state, bump) = Pubkey::find_program_address(&[b"factory", controller_state.as_ref()], &factory::ID);
+    let (_, authority_bump) = Pubkey::find_program_address(&[b"factory_authority", factory_state.as_ref()], &factory::ID);
+
+    // Discriminator, then admin, controller_state, controller_program and both counts, padded to 1000 bytes
+    let mut data = FactoryState::DISCRIMINATOR.to_vec();
+    data.extend_from_slice(admin.as_ref());
+    data.extend_from_slice(controller_state.as_ref());
+    data.extend_from_slice(controller::ID.as_ref());
+    data.extend_from_slice(&7u128.to_le_bytes());
+    data.extend_from_slice(&3u128.to_le_bytes());
+    data.resize(1000, 0);
+    runtime.set(factory_state, Account {
+        lamports: Rent::default().minimum_balance(1000),
+        data,
+        owner: factory::ID,
+        executable: false,
+    });
+
+    migrate(&mut runtime, factory_state, payer).unwrap();
+
+    assert_eq!(runtime.account(&factory_state).unwrap().data.len(), 8 + FactoryState::INIT_SPACE);
+
+    let state: FactoryState = runtime.state(&factory_state);
+    assert_eq!(state.version, factory::CURRENT_VERSION);
+    assert_eq!(state.admin, admin);
+    assert_eq!(state.controller_state, controller_state);
+    assert_eq!(state.controller_program, controller::ID);
+    assert_eq!(state.mint_request_count, 7);
+    assert_eq!(state.burn_request_count, 3);
+    assert_eq!(state.authority_bump, authority_bump);
+    assert_eq!(state.bump, bump);
+
+    assert_eq!(
+        migrate(&mut runtime, factory_state, payer).unwrap_err(),
+        ProgramError::from(anchor_lang::error::Error::from(Errors::AlreadyMigrated))
+    );
+}
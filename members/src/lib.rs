@@ -1,6 +1,107 @@
 use anchor_lang::prelude::*;
+use anchor_lang::Discriminator;
+use anchor_lang::solana_program::instruction::{AccountMeta, Instruction};
+use anchor_lang::solana_program::program::invoke_signed;
+use versioning::Upgrade;
 
-declare_id!("Fg6PaFpoGXkYsidMpWTK6W2BeZ7FEfcYkg476zPFsLnS");
+declare_id!("4JvDAmNs4HG2CzY1r89UqUr3gL1zuZFi1yQpGaZ2tqBb");
+
+/// Max signers in a multisig, keep in sync with its `max_len`
+pub const MAX_MULTISIG_OWNERS: usize = 10;
+
+/// Max accounts in a proposal's instruction, keep in sync with its `max_len`
+pub const MAX_PROPOSAL_ACCOUNTS: usize = 24;
+
+/// Max length of a proposal's instruction data, keep in sync with its `max_len`
+pub const MAX_PROPOSAL_DATA_LEN: usize = 512;
+
+/// Max length of a custodian label, keep in sync with its `max_len`
+pub const MAX_CUSTODIAN_LABEL_LEN: usize = 32;
+
+/// Max token mints a custodian can back, keep in sync with its `max_len`
+pub const MAX_CUSTODIAN_ASSETS: usize = 8;
+
+/// Max length of a merchant display name, keep in sync with its `max_len`
+pub const MAX_MERCHANT_NAME_LEN: usize = 64;
+
+/// Max length of a jurisdiction code (ISO 3166), keep in sync with its `max_len`
+pub const MAX_JURISDICTION_LEN: usize = 8;
+
+/// Max length of a KYC provider name, keep in sync with its `max_len`
+pub const MAX_KYC_PROVIDER_LEN: usize = 32;
+
+/// Layout version written to new accounts.
+///
+/// All three programs follow the same policy: state accounts start with a
+/// `version` byte and only grow by appending fields that read zero as their
+/// default, `migrate` reallocs them with a zeroed tail. Accounts from before
+/// versioning are recognised by their legacy padded size and rewritten field by
+/// field. Accounts that are replaced rather than upgraded stay unversioned:
+/// Proposal and PendingChange live until executed, FreezeRecord until thawed, and
+/// a Multisig or Reserves layout change ships as a new account type to move to.
+pub const CURRENT_VERSION: u8 = 5;
+
+/// Size unversioned Members accounts were allocated with
+const LEGACY_MEMBERS_SPACE: usize = 1000;
+
+/// Size unversioned Merchant accounts were allocated with
+const LEGACY_MERCHANT_SPACE: usize = 100;
+
+const _: () = assert!(8 + Members::INIT_SPACE != LEGACY_MEMBERS_SPACE);
+const _: () = assert!(8 + Merchant::INIT_SPACE != LEGACY_MERCHANT_SPACE);
+
+/// The controller program whose guardian may freeze merchants
+pub mod controller_program {
+    use anchor_lang::declare_id;
+
+    declare_id!("8VADzpWt5x3R4tZHrwqXPNQFey4praK48EzFh6hc2MUd");
+}
+
+/// The controller crate depends on this one, so Controller is read by layout.
+/// The controller's tests pin these to its real Borsh layout
+pub const CONTROLLER_DISCRIMINATOR: [u8; 8] = [184, 79, 171, 0, 183, 43, 113, 110];
+
+/// Byte range of Controller.members
+pub const CONTROLLER_MEMBERS_RANGE: std::ops::Range<usize> = 105..137;
+
+/// Byte range of Controller.guardian
+pub const CONTROLLER_GUARDIAN_RANGE: std::ops::Range<usize> = 318..350;
+
+/// Fails unless `controller_state` is a controller wired to `member_state`
+fn check_controller(controller_state: &AccountInfo, member_state: Pubkey) -> Result<()> {
+    if controller_state.owner != &controller_program::ID {
+        return Err(Errors::InvalidController.into())
+    }
+
+    let data = controller_state.try_borrow_data()?;
+    if data.len() < CONTROLLER_GUARDIAN_RANGE.end || data[..8] != CONTROLLER_DISCRIMINATOR {
+        return Err(Errors::InvalidController.into())
+    }
+
+    if data[CONTROLLER_MEMBERS_RANGE] != member_state.to_bytes() {
+        return Err(Errors::InvalidController.into())
+    }
+
+    Ok(())
+}
+
+fn check_owners(owners: &[Pubkey], threshold: u8) -> Result<()> {
+    if owners.is_empty() || owners.len() > MAX_MULTISIG_OWNERS {
+        return Err(Errors::InvalidOwners.into())
+    }
+
+    for (i, owner) in owners.iter().enumerate() {
+        if owners[..i].contains(owner) {
+            return Err(Errors::InvalidOwners.into())
+        }
+    }
+
+    if threshold == 0 || threshold as usize > owners.len() {
+        return Err(Errors::InvalidThreshold.into())
+    }
+
+    Ok(())
+}
 
 #[program]
 pub mod members {
@@ -9,7 +110,42 @@ pub mod members {
     pub fn initialize(ctx: Context<Initialize>) -> Result<()> {
         let member = &mut ctx.accounts.member_state;
 
+        member.version = CURRENT_VERSION;
         member.admin = ctx.accounts.admin.key();
+        member.bump = *ctx.bumps.get("member_state").unwrap();
+
+        emit!(MembersInitialized {
+            member_state: member.key(),
+            admin: member.admin,
+        });
+
+        Ok(())
+    }
+
+    pub fn transfer_admin(ctx: Context<TransferAdmin>) -> Result<()> {
+        let member = &mut ctx.accounts.member_state;
+        member.pending_admin = ctx.accounts.new_admin.key();
+
+        emit!(AdminTransferStarted {
+            member_state: member.key(),
+            admin: member.admin,
+            pending_admin: member.pending_admin,
+        });
+
+        Ok(())
+    }
+
+    pub fn accept_admin(ctx: Context<AcceptAdmin>) -> Result<()> {
+        let member = &mut ctx.accounts.member_state;
+        let previous_admin = member.admin;
+        member.admin = member.pending_admin;
+        member.pending_admin = Pubkey::default();
+
+        emit!(AdminTransferred {
+            member_state: member.key(),
+            previous_admin,
+            new_admin: member.admin,
+        });
 
         Ok(())
     }
@@ -19,15 +155,93 @@ pub mod members {
 
         member.custodian = ctx.accounts.custodian.key();
 
+        emit!(CustodianSet {
+            member_state: member.key(),
+            custodian: member.custodian,
+        });
+
+        Ok(())
+    }
+
+    /// Trusts `controller_state`'s guardian to freeze merchants
+    pub fn set_controller(ctx: Context<SetController>) -> Result<()> {
+        check_controller(&ctx.accounts.controller_state, ctx.accounts.member_state.key())?;
+
+        let member = &mut ctx.accounts.member_state;
+
+        member.controller = ctx.accounts.controller_state.key();
+
+        emit!(ControllerSet {
+            member_state: member.key(),
+            controller: member.controller,
+        });
+
         Ok(())
     }
 
     pub fn add_merchant(ctx: Context<AddMerchant>) -> Result<()> {
+        let member = &mut ctx.accounts.member_state;
         let merchant = &mut ctx.accounts.merchant_state;
 
+        merchant.version = CURRENT_VERSION;
         merchant.merchant = ctx.accounts.merchant.key();
         merchant.active = true;
-        merchant.bump = *ctx.bumps.get("merchant").unwrap();
+        merchant.bump = *ctx.bumps.get("merchant_state").unwrap();
+
+        // First registration, or a merchant added before the registry existed
+        if !merchant.indexed {
+            let merchant_index = ctx.accounts.merchant_index.as_mut().ok_or(Errors::MerchantIndexRequired)?;
+            merchant_index.version = CURRENT_VERSION;
+            merchant_index.merchant = merchant.merchant;
+            merchant_index.bump = *ctx.bumps.get("merchant_index").unwrap();
+
+            merchant.index = member.merchant_count;
+            merchant.indexed = true;
+            member.merchant_count += 1;
+        } else if ctx.accounts.merchant_index.is_some() {
+            // Would leave an empty slot at merchant_count that no later add can fill
+            return Err(Errors::MerchantAlreadyIndexed.into())
+        }
+
+        emit!(MerchantAdded {
+            member_state: member.key(),
+            merchant: merchant.merchant,
+            index: merchant.index,
+        });
+
+        Ok(())
+    }
+
+    pub fn update_merchant_profile(ctx: Context<UpdateMerchantProfile>, profile: MerchantProfile) -> Result<()> {
+        if profile.display_name.len() > MAX_MERCHANT_NAME_LEN
+            || profile.jurisdiction.len() > MAX_JURISDICTION_LEN
+            || profile.kyc_provider.len() > MAX_KYC_PROVIDER_LEN
+        {
+            return Err(Errors::InvalidProfile.into())
+        }
+
+        // 0 means no expiry
+        if profile.kyc_expires_at != 0 && profile.kyc_expires_at <= profile.onboarded_at {
+            return Err(Errors::InvalidProfile.into())
+        }
+
+        let merchant = &mut ctx.accounts.merchant_state;
+
+        merchant.display_name = profile.display_name;
+        merchant.jurisdiction = profile.jurisdiction;
+        merchant.kyc_provider = profile.kyc_provider;
+        merchant.attestation_hash = profile.attestation_hash;
+        merchant.onboarded_at = profile.onboarded_at;
+        merchant.kyc_expires_at = profile.kyc_expires_at;
+
+        emit!(MerchantProfileUpdated {
+            member_state: ctx.accounts.member_state.key(),
+            merchant: merchant.merchant,
+            kyc_provider: merchant.kyc_provider.clone(),
+            attestation_hash: merchant.attestation_hash,
+            onboarded_at: merchant.onboarded_at,
+            kyc_expires_at: merchant.kyc_expires_at,
+        });
 
         Ok(())
     }
@@ -37,29 +251,364 @@ pub mod members {
 
         merchant.active = false;
 
+        emit!(MerchantRemoved {
+            member_state: ctx.accounts.member_state.key(),
+            merchant: merchant.merchant,
+        });
+
+        Ok(())
+    }
+
+    /// Registers a custodian or updates an existing one, re-activating it if removed.
+    /// Only registered custodians can set deposit addresses or confirm requests, so
+    /// after migrating a Members account from before the registry the admin must add
+    /// its existing `custodian` here
+    pub fn add_custodian(ctx: Context<AddCustodian>, label: String, allowed_assets: Vec<Pubkey>) -> Result<()> {
+        if label.len() > MAX_CUSTODIAN_LABEL_LEN {
+            return Err(Errors::InvalidLabel.into())
+        }
+
+        if allowed_assets.len() > MAX_CUSTODIAN_ASSETS {
+            return Err(Errors::InvalidAssets.into())
+        }
+
+        let custodian = &mut ctx.accounts.custodian_state;
+
+        custodian.version = CURRENT_VERSION;
+        custodian.custodian = ctx.accounts.custodian.key();
+        custodian.active = true;
+        custodian.label = label;
+        custodian.allowed_assets = allowed_assets;
+        custodian.bump = *ctx.bumps.get("custodian_state").unwrap();
+
+        emit!(CustodianAdded {
+            member_state: ctx.accounts.member_state.key(),
+            custodian: custodian.custodian,
+            label: custodian.label.clone(),
+            allowed_assets: custodian.allowed_assets.clone(),
+        });
+
+        Ok(())
+    }
+
+    pub fn remove_custodian(ctx: Context<RemoveCustodian>) -> Result<()> {
+        let custodian = &mut ctx.accounts.custodian_state;
+
+        custodian.active = false;
+
+        emit!(CustodianRemoved {
+            member_state: ctx.accounts.member_state.key(),
+            custodian: custodian.custodian,
+        });
+
+        Ok(())
+    }
+
+    /// Frozen by the admin, or by the guardian of the controller the admin trusts
+    pub fn freeze_merchant(ctx: Context<FreezeMerchant>) -> Result<()> {
+        let member = &ctx.accounts.member_state;
+        if ctx.accounts.signer.key() != member.admin {
+            let controller_state = ctx.accounts.controller_state.as_ref().ok_or(Errors::SenderNotAuthorized)?;
+            if controller_state.key() != member.controller {
+                return Err(Errors::InvalidController.into())
+            }
+
+            check_controller(controller_state, member.key())?;
+
+            let data = controller_state.try_borrow_data()?;
+            if data[CONTROLLER_GUARDIAN_RANGE] != ctx.accounts.signer.key().to_bytes() {
+                return Err(Errors::SenderNotAuthorized.into())
+            }
+        }
+
+        let merchant = &mut ctx.accounts.merchant_state;
+
+        merchant.frozen = true;
+
+        emit!(MerchantFrozen {
+            member_state: ctx.accounts.member_state.key(),
+            merchant: merchant.merchant,
+            by: ctx.accounts.signer.key(),
+        });
+
+        Ok(())
+    }
+
+    pub fn unfreeze_merchant(ctx: Context<UnfreezeMerchant>) -> Result<()> {
+        let merchant = &mut ctx.accounts.merchant_state;
+
+        merchant.frozen = false;
+
+        emit!(MerchantUnfrozen {
+            member_state: ctx.accounts.member_state.key(),
+            merchant: merchant.merchant,
+        });
+
+        Ok(())
+    }
+
+    /// True while the merchant is registered, active and not frozen
+    pub fn is_merchant(ctx: Context<IsMerchant>, _merchant: Pubkey) -> Result<bool> {
+        let merchant_state = &ctx.accounts.merchant_state;
+        if merchant_state.owner != &crate::ID {
+            return Ok(false)
+        }
+
+        let merchant = Account::<Merchant>::try_from(merchant_state)?;
+
+        Ok(merchant.active && !merchant.frozen)
+    }
+
+    pub fn get_custodian(ctx: Context<GetCustodian>) -> Result<Pubkey> {
+        Ok(ctx.accounts.member_state.custodian)
+    }
+
+    pub fn create_multisig(ctx: Context<CreateMultisig>, owners: Vec<Pubkey>, threshold: u8) -> Result<()> {
+        check_owners(&owners, threshold)?;
+
+        let multisig = &mut ctx.accounts.multisig;
+        multisig.owners = owners;
+        multisig.threshold = threshold;
+        multisig.signer_bump = *ctx.bumps.get("multisig_signer").unwrap();
+        multisig.owner_set_seqno = 0;
+        multisig.proposal_count = 0;
+
+        emit!(MultisigOwnersSet {
+            multisig: multisig.key(),
+            signer: ctx.accounts.multisig_signer.key(),
+            owners: multisig.owners.clone(),
+            threshold,
+        });
+
+        Ok(())
+    }
+
+    pub fn create_proposal(ctx: Context<CreateProposal>, program_id: Pubkey, accounts: Vec<ProposalAccount>, data: Vec<u8>) -> Result<()> {
+        if accounts.len() > MAX_PROPOSAL_ACCOUNTS || data.len() > MAX_PROPOSAL_DATA_LEN {
+            return Err(Errors::ProposalTooLarge.into())
+        }
+
+        let multisig = &mut ctx.accounts.multisig;
+        let owner_index = multisig.owners.iter()
+            .position(|owner| *owner == ctx.accounts.proposer.key())
+            .ok_or(Errors::NotMultisigOwner)?;
+
+        let mut signers = vec![false; multisig.owners.len()];
+        signers[owner_index] = true;
+
+        let proposal = &mut ctx.accounts.proposal;
+        proposal.multisig = multisig.key();
+        proposal.program_id = program_id;
+        proposal.accounts = accounts;
+        proposal.data = data;
+        proposal.signers = signers;
+        proposal.owner_set_seqno = multisig.owner_set_seqno;
+        proposal.executed = false;
+        proposal.nonce = multisig.proposal_count;
+        proposal.bump = *ctx.bumps.get("proposal").unwrap();
+        multisig.proposal_count += 1;
+
+        emit!(ProposalCreated {
+            multisig: multisig.key(),
+            proposal: proposal.key(),
+            proposer: ctx.accounts.proposer.key(),
+            nonce: proposal.nonce,
+        });
+
+        Ok(())
+    }
+
+    pub fn approve_proposal(ctx: Context<ApproveProposal>) -> Result<()> {
+        let owner_index = ctx.accounts.multisig.owners.iter()
+            .position(|owner| *owner == ctx.accounts.owner.key())
+            .ok_or(Errors::NotMultisigOwner)?;
+
+        let proposal = &mut ctx.accounts.proposal;
+        proposal.signers[owner_index] = true;
+
+        emit!(ProposalApproved {
+            multisig: proposal.multisig,
+            proposal: proposal.key(),
+            owner: ctx.accounts.owner.key(),
+        });
+
+        Ok(())
+    }
+
+    pub fn execute_proposal(ctx: Context<ExecuteProposal>) -> Result<()> {
+        let multisig = &ctx.accounts.multisig;
+        let approvals = ctx.accounts.proposal.signers.iter().filter(|signed| **signed).count();
+
+        if approvals < multisig.threshold as usize {
+            return Err(Errors::NotEnoughApprovals.into())
+        }
+
+        let signer_key = ctx.accounts.multisig_signer.key();
+        let ix = Instruction {
+            program_id: ctx.accounts.proposal.program_id,
+            accounts: ctx.accounts.proposal.accounts.iter().map(|account| AccountMeta {
+                pubkey: account.pubkey,
+                is_signer: account.is_signer || account.pubkey == signer_key,
+                is_writable: account.is_writable,
+            }).collect(),
+            data: ctx.accounts.proposal.data.clone(),
+        };
+
+        let multisig_key = multisig.key();
+        let seeds = &[
+            b"multisig_signer".as_ref(),
+            multisig_key.as_ref(),
+            &[multisig.signer_bump],
+        ];
+        let signer = &[&seeds[..]];
+
+        invoke_signed(&ix, ctx.remaining_accounts, signer)?;
+
+        let proposal = &mut ctx.accounts.proposal;
+        proposal.executed = true;
+
+        emit!(ProposalExecuted {
+            multisig: multisig_key,
+            proposal: proposal.key(),
+        });
+
+        Ok(())
+    }
+
+    pub fn migrate(ctx: Context<Migrate>) -> Result<()> {
+        let account = &ctx.accounts.account;
+        if account.owner != &crate::ID {
+            return Err(Errors::InvalidMigration.into())
+        }
+
+        let (upgrade, space) = {
+            let data = account.try_borrow_data()?;
+            if data.len() < 9 {
+                return Err(Errors::InvalidMigration.into())
+            }
+
+            if data[..8] == Members::DISCRIMINATOR && data.len() == LEGACY_MEMBERS_SPACE {
+                let legacy = MembersV0::deserialize(&mut &data[8..]).map_err(|_| Errors::InvalidMigration)?;
+
+                (Upgrade::rewrite(&legacy.upgrade())?, 8 + Members::INIT_SPACE)
+            } else if data[..8] == Merchant::DISCRIMINATOR && data.len() == LEGACY_MERCHANT_SPACE {
+                let legacy = MerchantV0::deserialize(&mut &data[8..]).map_err(|_| Errors::InvalidMigration)?;
+
+                (Upgrade::rewrite(&legacy.upgrade())?, 8 + Merchant::INIT_SPACE)
+            } else {
+                let space = if data[..8] == Members::DISCRIMINATOR {
+                    8 + Members::INIT_SPACE
+                } else if data[..8] == Merchant::DISCRIMINATOR {
+                    8 + Merchant::INIT_SPACE
+                } else if data[..8] == Custodian::DISCRIMINATOR {
+                    8 + Custodian::INIT_SPACE
+                } else if data[..8] == MerchantIndex::DISCRIMINATOR {
+                    8 + MerchantIndex::INIT_SPACE
+                } else {
+                    return Err(Errors::InvalidMigration.into())
+                };
+
+                (Upgrade::Grow(data[8]), space)
+            }
+        };
+
+        let from_version = upgrade.from_version();
+        if from_version >= CURRENT_VERSION {
+            return Err(Errors::AlreadyMigrated.into())
+        }
+
+        versioning::apply(account, &ctx.accounts.payer, &ctx.accounts.system_program, upgrade, space, CURRENT_VERSION)?;
+
+        emit!(Migrated {
+            account: account.key(),
+            from_version,
+            to_version: CURRENT_VERSION,
+        });
+
+        Ok(())
+    }
+
+    /// Only callable by the multisig signer itself, i.e. through an executed proposal
+    pub fn set_multisig_owners(ctx: Context<SetMultisigOwners>, owners: Vec<Pubkey>, threshold: u8) -> Result<()> {
+        check_owners(&owners, threshold)?;
+
+        let multisig = &mut ctx.accounts.multisig;
+        multisig.owners = owners;
+        multisig.threshold = threshold;
+        // Outstanding proposals were approved by the old owner set
+        multisig.owner_set_seqno += 1;
+
+        emit!(MultisigOwnersSet {
+            multisig: multisig.key(),
+            signer: ctx.accounts.multisig_signer.key(),
+            owners: multisig.owners.clone(),
+            threshold,
+        });
+
         Ok(())
     }
-    
 }
 
 #[derive(Accounts)]
 pub struct Initialize<'info> {
     #[account(
         init,
-        space = 1000,
+        seeds = [
+            b"members".as_ref()
+        ],
+        bump,
+        space = 8 + Members::INIT_SPACE,
         payer = payer
     )]
     pub member_state: Account<'info, Members>,
 
+    /// Upgrade authority, so nobody can front-run the singleton
     #[account(mut)]
     pub payer: Signer<'info>,
 
-    /// CHECK: TODO. Probably Small DAO
+    #[account(
+        constraint = program.programdata_address()? == Some(program_data.key())
+    )]
+    pub program: Program<'info, crate::program::Members>,
+
+    #[account(
+        constraint = program_data.upgrade_authority_address == Some(payer.key()) @ Errors::SenderNotAuthorized
+    )]
+    pub program_data: Account<'info, ProgramData>,
+
+    /// CHECK: Admin key, e.g. the signer PDA of a multisig
     pub admin: UncheckedAccount<'info>,
 
     pub system_program: Program<'info, System>
 }
 
+#[derive(Accounts)]
+pub struct TransferAdmin<'info> {
+    #[account(
+        mut,
+        has_one = admin
+    )]
+    pub member_state: Account<'info, Members>,
+
+    /// CHECK: New admin, only recorded as pending until it signs accept_admin
+    pub new_admin: UncheckedAccount<'info>,
+
+    #[account(mut)]
+    pub admin: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct AcceptAdmin<'info> {
+    #[account(
+        mut,
+        constraint = signer.key() == member_state.pending_admin @ Errors::NotPendingAdmin
+    )]
+    pub member_state: Account<'info, Members>,
+
+    #[account(mut)]
+    pub signer: Signer<'info>,
+}
+
 #[derive(Accounts)]
 pub struct SetCustodian<'info> {
     #[account(
@@ -74,6 +623,21 @@ pub struct SetCustodian<'info> {
     pub custodian: UncheckedAccount<'info>
 }
 
+#[derive(Accounts)]
+pub struct SetController<'info> {
+    #[account(
+        mut,
+        has_one = admin
+    )]
+    pub member_state: Account<'info, Members>,
+
+    #[account(mut)]
+    pub admin: Signer<'info>,
+
+    /// CHECK: Read by layout in set_controller
+    pub controller_state: UncheckedAccount<'info>
+}
+
 #[derive(Accounts)]
 pub struct AddMerchant<'info> {
     #[account(
@@ -94,12 +658,26 @@ pub struct AddMerchant<'info> {
             member_state.key().as_ref(),
             merchant.key().as_ref()
         ],
-        space = 100,
+        space = 8 + Merchant::INIT_SPACE,
         payer = admin,
         bump,
     )]
     pub merchant_state: Account<'info, Merchant>,
 
+    /// Next registry slot, only required the first time a merchant is added
+    #[account(
+        init,
+        seeds = [
+            b"merchant_index".as_ref(),
+            member_state.key().as_ref(),
+            member_state.merchant_count.to_le_bytes().as_ref()
+        ],
+        space = 8 + MerchantIndex::INIT_SPACE,
+        payer = admin,
+        bump,
+    )]
+    pub merchant_index: Option<Account<'info, MerchantIndex>>,
+
     pub system_program: Program<'info, System>
 }
 
@@ -128,22 +706,639 @@ pub struct RemoveMerchant<'info> {
     pub merchant_state: Account<'info, Merchant>,
 }
 
+#[derive(Accounts)]
+pub struct UpdateMerchantProfile<'info> {
+    #[account(
+        has_one = admin
+    )]
+    pub member_state: Account<'info, Members>,
+
+    #[account(mut)]
+    pub admin: Signer<'info>,
+
+    /// CHECK: Merchant key, only used as a seed
+    pub merchant: UncheckedAccount<'info>,
+
+    #[account(
+        mut,
+        seeds = [
+            b"merchant".as_ref(),
+            member_state.key().as_ref(),
+            merchant.key().as_ref()
+        ],
+        bump = merchant_state.bump,
+    )]
+    pub merchant_state: Account<'info, Merchant>,
+}
+
+#[derive(Accounts)]
+pub struct AddCustodian<'info> {
+    #[account(
+        has_one = admin
+    )]
+    pub member_state: Account<'info, Members>,
+
+    #[account(mut)]
+    pub admin: Signer<'info>,
+
+    /// CHECK: Custodian key, only recorded
+    pub custodian: UncheckedAccount<'info>,
+
+    #[account(
+        init_if_needed,
+        seeds = [
+            b"custodian".as_ref(),
+            member_state.key().as_ref(),
+            custodian.key().as_ref()
+        ],
+        space = 8 + Custodian::INIT_SPACE,
+        payer = admin,
+        bump,
+    )]
+    pub custodian_state: Account<'info, Custodian>,
+
+    pub system_program: Program<'info, System>
+}
+
+#[derive(Accounts)]
+pub struct RemoveCustodian<'info> {
+    #[account(
+        has_one = admin
+    )]
+    pub member_state: Account<'info, Members>,
+
+    #[account(mut)]
+    pub admin: Signer<'info>,
+
+    /// CHECK: Custodian key, only used as a seed
+    pub custodian: UncheckedAccount<'info>,
+
+    #[account(
+        mut,
+        seeds = [
+            b"custodian".as_ref(),
+            member_state.key().as_ref(),
+            custodian.key().as_ref()
+        ],
+        bump = custodian_state.bump,
+    )]
+    pub custodian_state: Account<'info, Custodian>,
+}
+
+#[derive(Accounts)]
+pub struct FreezeMerchant<'info> {
+    pub member_state: Account<'info, Members>,
+
+    pub signer: Signer<'info>,
+
+    /// CHECK: The trusted controller, read by layout. Omitted when the admin freezes
+    pub controller_state: Option<UncheckedAccount<'info>>,
+
+    pub merchant: UncheckedAccount<'info>,
+
+    #[account(
+        mut,
+        seeds = [
+            b"merchant".as_ref(),
+            member_state.key().as_ref(),
+            merchant.key().as_ref()
+        ],
+        bump = merchant_state.bump,
+    )]
+    pub merchant_state: Account<'info, Merchant>,
+}
+
+#[derive(Accounts)]
+pub struct UnfreezeMerchant<'info> {
+    #[account(
+        has_one = admin
+    )]
+    pub member_state: Account<'info, Members>,
+
+    #[account(mut)]
+    pub admin: Signer<'info>,
+
+    pub merchant: UncheckedAccount<'info>,
+
+    #[account(
+        mut,
+        seeds = [
+            b"merchant".as_ref(),
+            member_state.key().as_ref(),
+            merchant.key().as_ref()
+        ],
+        bump = merchant_state.bump,
+    )]
+    pub merchant_state: Account<'info, Merchant>,
+}
+
+#[derive(Accounts)]
+#[instruction(merchant: Pubkey)]
+pub struct IsMerchant<'info> {
+    pub member_state: Account<'info, Members>,
+
+    /// CHECK: Merchant PDA, may not exist
+    #[account(
+        seeds = [
+            b"merchant".as_ref(),
+            member_state.key().as_ref(),
+            merchant.as_ref()
+        ],
+        bump,
+    )]
+    pub merchant_state: UncheckedAccount<'info>,
+}
+
+#[derive(Accounts)]
+pub struct GetCustodian<'info> {
+    pub member_state: Account<'info, Members>,
+}
+
+#[derive(Accounts)]
+pub struct Migrate<'info> {
+    /// CHECK: Owner and discriminator are checked in migrate
+    #[account(mut)]
+    pub account: UncheckedAccount<'info>,
+
+    /// Covers any extra rent, migrating is permissionless
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    pub system_program: Program<'info, System>
+}
+
+#[derive(Accounts)]
+pub struct CreateMultisig<'info> {
+    #[account(
+        init,
+        space = 8 + Multisig::INIT_SPACE,
+        payer = payer
+    )]
+    pub multisig: Account<'info, Multisig>,
+
+    /// CHECK: PDA that signs for the multisig, set it as Controller.owner, Members.admin or FactoryState.admin
+    #[account(
+        seeds = [
+            b"multisig_signer".as_ref(),
+            multisig.key().as_ref()
+        ],
+        bump,
+    )]
+    pub multisig_signer: UncheckedAccount<'info>,
+
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    pub system_program: Program<'info, System>
+}
+
+#[derive(Accounts)]
+pub struct CreateProposal<'info> {
+    #[account(mut)]
+    pub multisig: Account<'info, Multisig>,
+
+    #[account(
+        init,
+        seeds = [
+            b"proposal".as_ref(),
+            multisig.key().as_ref(),
+            multisig.proposal_count.to_le_bytes().as_ref()
+        ],
+        bump,
+        payer = proposer,
+        space = 8 + Proposal::INIT_SPACE
+    )]
+    pub proposal: Account<'info, Proposal>,
+
+    #[account(mut)]
+    pub proposer: Signer<'info>,
+
+    pub system_program: Program<'info, System>
+}
+
+#[derive(Accounts)]
+pub struct ApproveProposal<'info> {
+    pub multisig: Account<'info, Multisig>,
+
+    #[account(
+        mut,
+        has_one = multisig,
+        constraint = !proposal.executed @ Errors::ProposalAlreadyExecuted,
+        constraint = proposal.owner_set_seqno == multisig.owner_set_seqno @ Errors::StaleProposal
+    )]
+    pub proposal: Account<'info, Proposal>,
+
+    pub owner: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct ExecuteProposal<'info> {
+    pub multisig: Account<'info, Multisig>,
+
+    #[account(
+        mut,
+        has_one = multisig,
+        constraint = !proposal.executed @ Errors::ProposalAlreadyExecuted,
+        constraint = proposal.owner_set_seqno == multisig.owner_set_seqno @ Errors::StaleProposal
+    )]
+    pub proposal: Account<'info, Proposal>,
+
+    /// CHECK: PDA that signs the proposal's instruction
+    #[account(
+        seeds = [
+            b"multisig_signer".as_ref(),
+            multisig.key().as_ref()
+        ],
+        bump = multisig.signer_bump,
+    )]
+    pub multisig_signer: UncheckedAccount<'info>,
+}
+
+#[derive(Accounts)]
+pub struct SetMultisigOwners<'info> {
+    #[account(mut)]
+    pub multisig: Account<'info, Multisig>,
+
+    #[account(
+        seeds = [
+            b"multisig_signer".as_ref(),
+            multisig.key().as_ref()
+        ],
+        bump = multisig.signer_bump,
+    )]
+    pub multisig_signer: Signer<'info>,
+}
+
+/// Members layout from before versioning
+#[derive(AnchorDeserialize)]
+struct MembersV0 {
+    admin: Pubkey,
+    custodian: Pubkey,
+}
+
+impl MembersV0 {
+    fn upgrade(self) -> Members {
+        Members {
+            version: CURRENT_VERSION,
+            admin: self.admin,
+            custodian: self.custodian,
+            bump: Pubkey::find_program_address(&[b"members".as_ref()], &crate::ID).1,
+            controller: Pubkey::default(),
+            pending_admin: Pubkey::default(),
+            merchant_count: 0,
+        }
+    }
+}
+
+/// Merchant layout from before versioning
+#[derive(AnchorDeserialize)]
+struct MerchantV0 {
+    merchant: Pubkey,
+    active: bool,
+    bump: u8,
+}
+
+impl MerchantV0 {
+    fn upgrade(self) -> Merchant {
+        Merchant {
+            version: CURRENT_VERSION,
+            merchant: self.merchant,
+            active: self.active,
+            bump: self.bump,
+            frozen: false,
+            index: 0,
+            indexed: false,
+            display_name: String::new(),
+            jurisdiction: String::new(),
+            kyc_provider: String::new(),
+            attestation_hash: [0; 32],
+            onboarded_at: 0,
+            kyc_expires_at: 0,
+        }
+    }
+}
+
 #[account]
+#[derive(InitSpace)]
 pub struct Members {
+    /// Layout Version
+    pub version: u8,
     /// Admin
     pub admin: Pubkey,
-    /// Custodian
+    /// Primary Custodian - attests reserves, all custodians live in the registry
     pub custodian: Pubkey,
+
+    /// Seed Bump
+    pub bump: u8,
+
+    /// Controller whose guardian may freeze merchants - default until the admin sets one
+    pub controller: Pubkey,
+
+    /// Pending Admin - must accept before becoming admin
+    pub pending_admin: Pubkey,
+
+    /// Merchants in the registry, i.e. the next merchant index
+    pub merchant_count: u64,
 }
 
 #[account]
+#[derive(InitSpace)]
 pub struct Merchant {
+    /// Layout Version
+    pub version: u8,
+
     /// Merchant
     pub merchant: Pubkey,
 
     /// Merchant State
     pub active: bool,
 
+    /// Seed Bump
+    pub bump: u8,
+
+    /// Frozen by the admin or guardian
+    pub frozen: bool,
+
+    /// Slot in the merchant registry
+    pub index: u64,
+
+    /// Whether a MerchantIndex entry points at this merchant
+    pub indexed: bool,
+
+    /// Display Name
+    #[max_len(64)]
+    pub display_name: String,
+
+    /// Jurisdiction Code (ISO 3166)
+    #[max_len(8)]
+    pub jurisdiction: String,
+
+    /// KYC Provider
+    #[max_len(32)]
+    pub kyc_provider: String,
+
+    /// Hash of the KYC/AML attestation document
+    pub attestation_hash: [u8; 32],
+
+    /// Onboarding Timestamp
+    pub onboarded_at: i64,
+
+    /// KYC Expiry Timestamp - 0 means no expiry
+    pub kyc_expires_at: i64,
+}
+
+/// Argument of update_merchant_profile
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug)]
+pub struct MerchantProfile {
+    pub display_name: String,
+    pub jurisdiction: String,
+    pub kyc_provider: String,
+    pub attestation_hash: [u8; 32],
+    pub onboarded_at: i64,
+    pub kyc_expires_at: i64,
+}
+
+/// Registry entry at ["merchant_index", member_state, index], enumerate
+/// 0..Members.merchant_count and read each Merchant for its status
+#[account]
+#[derive(InitSpace)]
+pub struct MerchantIndex {
+    /// Layout Version
+    pub version: u8,
+
+    /// Merchant
+    pub merchant: Pubkey,
+
+    /// Seed Bump
+    pub bump: u8,
+}
+
+#[account]
+#[derive(InitSpace)]
+pub struct Custodian {
+    /// Layout Version
+    pub version: u8,
+
+    /// Custodian
+    pub custodian: Pubkey,
+
+    /// Custodian State
+    pub active: bool,
+
+    /// Display Label
+    #[max_len(32)]
+    pub label: String,
+
+    /// Token mints this custodian may confirm mints and burns for
+    #[max_len(8)]
+    pub allowed_assets: Vec<Pubkey>,
+
+    /// Seed Bump
+    pub bump: u8,
+}
+
+#[account]
+#[derive(InitSpace)]
+pub struct Multisig {
+    /// Signer Set
+    #[max_len(10)]
+    pub owners: Vec<Pubkey>,
+    /// Approvals needed to execute a proposal
+    pub threshold: u8,
+    /// Multisig Signer PDA Bump
+    pub signer_bump: u8,
+    /// Bumped whenever the owner set changes
+    pub owner_set_seqno: u32,
+    /// Proposal Count
+    pub proposal_count: u64,
+}
+
+#[account]
+#[derive(InitSpace)]
+pub struct Proposal {
+    /// Multisig
+    pub multisig: Pubkey,
+    /// Program to invoke
+    pub program_id: Pubkey,
+    /// Accounts for the instruction
+    #[max_len(24)]
+    pub accounts: Vec<ProposalAccount>,
+    /// Instruction Data
+    #[max_len(512)]
+    pub data: Vec<u8>,
+    /// Approvals, indexed like Multisig.owners
+    #[max_len(10)]
+    pub signers: Vec<bool>,
+    /// Owner set the approvals belong to
+    pub owner_set_seqno: u32,
+    /// Executed State
+    pub executed: bool,
+    /// Proposal Number
+    pub nonce: u64,
+
     /// Seed Bump
     pub bump: u8
-}
\ No newline at end of file
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, InitSpace)]
+pub struct ProposalAccount {
+    pub pubkey: Pubkey,
+    pub is_signer: bool,
+    pub is_writable: bool,
+}
+
+#[event]
+pub struct MembersInitialized {
+    pub member_state: Pubkey,
+    pub admin: Pubkey,
+}
+
+#[event]
+pub struct AdminTransferStarted {
+    pub member_state: Pubkey,
+    pub admin: Pubkey,
+    pub pending_admin: Pubkey,
+}
+
+#[event]
+pub struct AdminTransferred {
+    pub member_state: Pubkey,
+    pub previous_admin: Pubkey,
+    pub new_admin: Pubkey,
+}
+
+#[event]
+pub struct CustodianSet {
+    pub member_state: Pubkey,
+    pub custodian: Pubkey,
+}
+
+#[event]
+pub struct ControllerSet {
+    pub member_state: Pubkey,
+    pub controller: Pubkey,
+}
+
+#[event]
+pub struct CustodianAdded {
+    pub member_state: Pubkey,
+    pub custodian: Pubkey,
+    pub label: String,
+    pub allowed_assets: Vec<Pubkey>,
+}
+
+#[event]
+pub struct CustodianRemoved {
+    pub member_state: Pubkey,
+    pub custodian: Pubkey,
+}
+
+#[event]
+pub struct MerchantAdded {
+    pub member_state: Pubkey,
+    pub merchant: Pubkey,
+    pub index: u64,
+}
+
+#[event]
+pub struct MerchantProfileUpdated {
+    pub member_state: Pubkey,
+    pub merchant: Pubkey,
+    pub kyc_provider: String,
+    pub attestation_hash: [u8; 32],
+    pub onboarded_at: i64,
+    pub kyc_expires_at: i64,
+}
+
+#[event]
+pub struct MerchantRemoved {
+    pub member_state: Pubkey,
+    pub merchant: Pubkey,
+}
+
+#[event]
+pub struct MerchantFrozen {
+    pub member_state: Pubkey,
+    pub merchant: Pubkey,
+    pub by: Pubkey,
+}
+
+#[event]
+pub struct MerchantUnfrozen {
+    pub member_state: Pubkey,
+    pub merchant: Pubkey,
+}
+
+#[event]
+pub struct MultisigOwnersSet {
+    pub multisig: Pubkey,
+    pub signer: Pubkey,
+    pub owners: Vec<Pubkey>,
+    pub threshold: u8,
+}
+
+#[event]
+pub struct ProposalCreated {
+    pub multisig: Pubkey,
+    pub proposal: Pubkey,
+    pub proposer: Pubkey,
+    pub nonce: u64,
+}
+
+#[event]
+pub struct ProposalApproved {
+    pub multisig: Pubkey,
+    pub proposal: Pubkey,
+    pub owner: Pubkey,
+}
+
+#[event]
+pub struct ProposalExecuted {
+    pub multisig: Pubkey,
+    pub proposal: Pubkey,
+}
+
+#[event]
+pub struct Migrated {
+    pub account: Pubkey,
+    pub from_version: u8,
+    pub to_version: u8,
+}
+
+#[error_code]
+pub enum Errors {
+    #[msg("sender not authorized.")]
+    SenderNotAuthorized,
+    #[msg("owners must be unique, non-empty and within the max.")]
+    InvalidOwners,
+    #[msg("threshold must be between 1 and the number of owners.")]
+    InvalidThreshold,
+    #[msg("signer is not a multisig owner.")]
+    NotMultisigOwner,
+    #[msg("not enough approvals to execute.")]
+    NotEnoughApprovals,
+    #[msg("proposal already executed.")]
+    ProposalAlreadyExecuted,
+    #[msg("proposal predates the current owner set.")]
+    StaleProposal,
+    #[msg("account can't be migrated by this program.")]
+    InvalidMigration,
+    #[msg("account is already at the current version.")]
+    AlreadyMigrated,
+    #[msg("signer is not the pending admin.")]
+    NotPendingAdmin,
+    #[msg("custodian label is too long.")]
+    InvalidLabel,
+    #[msg("too many allowed assets.")]
+    InvalidAssets,
+    #[msg("merchant_index is required to register a new merchant.")]
+    MerchantIndexRequired,
+    #[msg("merchant profile field too long or expiry before onboarding.")]
+    InvalidProfile,
+    #[msg("proposal has too many accounts or too much data.")]
+    ProposalTooLarge,
+    #[msg("merchant is already in the registry, don't pass merchant_index.")]
+    MerchantAlreadyIndexed,
+    #[msg("controller is not wired to this registry.")]
+    InvalidController,
+}
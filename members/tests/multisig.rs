@@ -0,0 +1,285 @@
+use anchor_lang::prelude::*;
+use anchor_lang::solana_program::entrypoint::ProgramResult;
+use anchor_lang::solana_program::instruction::Instruction;
+use anchor_lang::solana_program::system_program;
+use anchor_lang::{InstructionData, ToAccountMetas};
+use test_runtime::{Entry, Runtime};
+use members::{Errors, Members, Multisig, Proposal, ProposalAccount};
+
+fn pda(seeds: &[&[u8]], program_id: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(seeds, program_id)
+}
+
+fn err(error: impl Into<anchor_lang::error::Error>) -> ProgramError {
+    error.into().into()
+}
+
+struct Fixture {
+    runtime: Runtime,
+    owners: Vec<Pubkey>,
+    multisig: Pubkey,
+    multisig_signer: Pubkey,
+    member_state: Pubkey,
+}
+
+impl Fixture {
+    /// A 2-of-3 multisig that administers the members registry
+    fn new() -> Self {
+        let mut runtime = Runtime::new(&[(members::ID, members::entry as Entry)]);
+
+        let owners = vec![Pubkey::new_unique(), Pubkey::new_unique(), Pubkey::new_unique()];
+        for owner in &owners {
+            runtime.fund(*owner);
+        }
+
+        let multisig = Pubkey::new_unique();
+        let multisig_signer = pda(&[b"multisig_signer", multisig.as_ref()], &members::ID).0;
+
+        let (member_state, bump) = pda(&[b"members"], &members::ID);
+        runtime.set_state(member_state, members::ID, 8 + Members::INIT_SPACE, &Members {
+            version: members::CURRENT_VERSION,
+            admin: multisig_signer,
+            custodian: Pubkey::new_unique(),
+            bump,
+            controller: Pubkey::default(),
+            pending_admin: Pubkey::default(),
+            merchant_count: 0,
+        });
+
+        let mut fixture = Fixture {
+            runtime,
+            owners,
+            multisig,
+            multisig_signer,
+            member_state,
+        };
+
+        let owners = fixture.owners.clone();
+        fixture.process(
+            members::accounts::CreateMultisig {
+                multisig,
+                multisig_signer,
+                payer: owners[0],
+                system_program: system_program::ID,
+            },
+            members::instruction::CreateMultisig { owners, threshold: 2 },
+        ).unwrap();
+
+        fixture
+    }
+
+    fn process(&mut self, accounts: impl ToAccountMetas, data: impl InstructionData) -> ProgramResult {
+        self.runtime.process(Instruction {
+            program_id: members::ID,
+            accounts: accounts.to_account_metas(None),
+            data: data.data(),
+        })
+    }
+
+    fn proposal(&self, nonce: u64) -> Pubkey {
+        pda(&[b"proposal", self.multisig.as_ref(), nonce.to_le_bytes().as_ref()], &members::ID).0
+    }
+
+    /// Proposes a call the multisig signer makes on the members program
+    fn create_proposal(&mut self, proposer: Pubkey, accounts: Vec<AccountMeta>, data: impl InstructionData) -> std::result::Result<Pubkey, ProgramError> {
+        let nonce = self.runtime.state::<Multisig>(&self.multisig).proposal_count;
+        let proposal = self.proposal(nonce);
+
+        self.process(
+            members::accounts::CreateProposal {
+                multisig: self.multisig,
+                proposal,
+                proposer,
+                system_program: system_program::ID,
+            },
+            members::instruction::CreateProposal {
+                program_id: members::ID,
+                accounts: accounts.iter().map(|meta| ProposalAccount {
+                    pubkey: meta.pubkey,
+                    is_signer: meta.is_signer,
+                    is_writable: meta.is_writable,
+                }).collect(),
+                data: data.data(),
+            },
+        )?;
+
+        Ok(proposal)
+    }
+
+    fn propose_custodian(&mut self, proposer: Pubkey, custodian: Pubkey) -> Pubkey {
+        let accounts = members::accounts::SetCustodian {
+            member_state: self.member_state,
+            admin: self.multisig_signer,
+            custodian,
+        }.to_account_metas(None);
+
+        self.create_proposal(proposer, accounts, members::instruction::SetCustodian {}).unwrap()
+    }
+
+    fn propose_owners(&mut self, proposer: Pubkey, owners: Vec<Pubkey>, threshold: u8) -> Pubkey {
+        let accounts = members::accounts::SetMultisigOwners {
+            multisig: self.multisig,
+            multisig_signer: self.multisig_signer,
+        }.to_account_metas(None);
+
+        self.create_proposal(proposer, accounts, members::instruction::SetMultisigOwners { owners, threshold }).unwrap()
+    }
+
+    fn approve(&mut self, proposal: Pubkey, owner: Pubkey) -> ProgramResult {
+        self.process(
+            members::accounts::ApproveProposal {
+                multisig: self.multisig,
+                proposal,
+                owner,
+            },
+            members::instruction::ApproveProposal {},
+        )
+    }
+
+    fn execute(&mut self, proposal: Pubkey) -> ProgramResult {
+        let mut accounts = members::accounts::ExecuteProposal {
+            multisig: self.multisig,
+            proposal,
+            multisig_signer: self.multisig_signer,
+        }.to_account_metas(None);
+
+        // The proposal's own accounts, the signer is left unsigned for the program to sign
+        let stored = self.runtime.state::<Proposal>(&proposal);
+        accounts.extend(stored.accounts.iter().map(|account| AccountMeta {
+            pubkey: account.pubkey,
+            is_signer: account.is_signer && account.pubkey != self.multisig_signer,
+            is_writable: account.is_writable,
+        }));
+        accounts.push(AccountMeta::new_readonly(members::ID, false));
+
+        self.runtime.process(Instruction {
+            program_id: members::ID,
+            accounts,
+            data: members::instruction::ExecuteProposal {}.data(),
+        })
+    }
+
+    fn custodian(&self) -> Pubkey {
+        self.runtime.state::<Members>(&self.member_state).custodian
+    }
+}
+
+#[test]
+fn executed_proposal_signs_members_admin_call() {
+    let mut fixture = Fixture::new();
+    let (first, second) = (fixture.owners[0], fixture.owners[1]);
+    let custodian = Pubkey::new_unique();
+
+    let proposal = fixture.propose_custodian(first, custodian);
+    fixture.approve(proposal, second).unwrap();
+    fixture.execute(proposal).unwrap();
+
+    assert_eq!(fixture.custodian(), custodian);
+    assert!(fixture.runtime.state::<Proposal>(&proposal).executed);
+}
+
+#[test]
+fn proposal_below_threshold_is_not_executed() {
+    let mut fixture = Fixture::new();
+    let first = fixture.owners[0];
+    let previous = fixture.custodian();
+
+    // The proposer's own approval is one of the two needed
+    let proposal = fixture.propose_custodian(first, Pubkey::new_unique());
+
+    assert_eq!(fixture.execute(proposal), Err(err(Errors::NotEnoughApprovals)));
+    assert_eq!(fixture.custodian(), previous);
+
+    // Approving twice from the same owner doesn't count twice
+    fixture.approve(proposal, first).unwrap();
+    assert_eq!(fixture.execute(proposal), Err(err(Errors::NotEnoughApprovals)));
+}
+
+#[test]
+fn non_owner_cannot_propose_or_approve() {
+    let mut fixture = Fixture::new();
+    let first = fixture.owners[0];
+    let outsider = Pubkey::new_unique();
+    fixture.runtime.fund(outsider);
+
+    let accounts = members::accounts::SetCustodian {
+        member_state: fixture.member_state,
+        admin: fixture.multisig_signer,
+        custodian: outsider,
+    }.to_account_metas(None);
+    assert_eq!(
+        fixture.create_proposal(outsider, accounts, members::instruction::SetCustodian {}),
+        Err(err(Errors::NotMultisigOwner))
+    );
+
+    let proposal = fixture.propose_custodian(first, outsider);
+    assert_eq!(fixture.approve(proposal, outsider), Err(err(Errors::NotMultisigOwner)));
+    assert_eq!(fixture.execute(proposal), Err(err(Errors::NotEnoughApprovals)));
+}
+
+#[test]
+fn executed_proposal_cannot_run_again() {
+    let mut fixture = Fixture::new();
+    let (first, second, third) = (fixture.owners[0], fixture.owners[1], fixture.owners[2]);
+
+    let proposal = fixture.propose_custodian(first, Pubkey::new_unique());
+    fixture.approve(proposal, second).unwrap();
+    fixture.execute(proposal).unwrap();
+
+    assert_eq!(fixture.execute(proposal), Err(err(Errors::ProposalAlreadyExecuted)));
+    assert_eq!(fixture.approve(proposal, third), Err(err(Errors::ProposalAlreadyExecuted)));
+}
+
+#[test]
+fn changing_owners_invalidates_outstanding_proposals() {
+    let mut fixture = Fixture::new();
+    let (first, second, third) = (fixture.owners[0], fixture.owners[1], fixture.owners[2]);
+    let previous = fixture.custodian();
+
+    let outstanding = fixture.propose_custodian(first, Pubkey::new_unique());
+    fixture.approve(outstanding, second).unwrap();
+
+    // Drop the third owner through the multisig itself
+    let owners = vec![first, second];
+    let rotation = fixture.propose_owners(first, owners.clone(), 2);
+    fixture.approve(rotation, second).unwrap();
+    fixture.execute(rotation).unwrap();
+
+    let multisig = fixture.runtime.state::<Multisig>(&fixture.multisig);
+    assert_eq!(multisig.owners, owners);
+    assert_eq!(multisig.owner_set_seqno, 1);
+
+    assert_eq!(fixture.execute(outstanding), Err(err(Errors::StaleProposal)));
+    assert_eq!(fixture.approve(outstanding, second), Err(err(Errors::StaleProposal)));
+    assert_eq!(fixture.custodian(), previous);
+
+    // The removed owner can't propose under the new set
+    let accounts = members::accounts::SetCustodian {
+        member_state: fixture.member_state,
+        admin: fixture.multisig_signer,
+        custodian: third,
+    }.to_account_metas(None);
+    assert_eq!(
+        fixture.create_proposal(third, accounts, members::instruction::SetCustodian {}),
+        Err(err(Errors::NotMultisigOwner))
+    );
+}
+
+#[test]
+fn owners_cannot_be_set_without_the_multisig_signer() {
+    let mut fixture = Fixture::new();
+    let first = fixture.owners[0];
+
+    let result = fixture.process(
+        members::accounts::SetMultisigOwners {
+            multisig: fixture.multisig,
+            multisig_signer: fixture.multisig_signer,
+        }.to_account_metas(None).into_iter().map(|mut meta| {
+            meta.is_signer = false;
+            meta
+        }).collect::<Vec<_>>(),
+        members::instruction::SetMultisigOwners { owners: vec![first], threshold: 1 },
+    );
+
+    assert_eq!(result, Err(err(ErrorCode::AccountNotSigner)));
+}
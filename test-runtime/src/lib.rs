@@ -0,0 +1,323 @@
+//! Runs program instructions in-process for the programs' tests.
+//!
+//! Accounts are laid out the way the BPF loader serializes them, so `realloc` and
+//! `assign` see the same memory a deployed program does. CPIs go through the syscall
+//! stubs: the system program is emulated, SPL Token runs its own processor and any
+//! other program is looked up among the ones the runtime was built with. PDA
+//! signatures are checked against the calling program, like the runtime does.
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::sync::Once;
+
+use anchor_lang::prelude::*;
+use anchor_lang::solana_program::entrypoint::{deserialize, ProgramResult, MAX_PERMITTED_DATA_INCREASE, NON_DUP_MARKER, SUCCESS};
+use anchor_lang::solana_program::instruction::Instruction;
+use anchor_lang::solana_program::program_pack::{IsInitialized, Pack};
+use anchor_lang::solana_program::program_stubs::{set_syscall_stubs, SyscallStubs};
+use anchor_lang::solana_program::program_utils::limited_deserialize;
+use anchor_lang::solana_program::{bpf_loader, system_instruction::SystemInstruction, system_program};
+
+pub use spl_token;
+
+/// Unix timestamp the clock sysvar starts at
+pub const NOW: i64 = 1_700_000_000;
+
+/// A program's entrypoint, e.g. the `entry` Anchor generates
+pub type Entry = fn(&Pubkey, &[AccountInfo], &[u8]) -> ProgramResult;
+
+thread_local! {
+    static PROGRAMS: RefCell<Vec<(Pubkey, Entry)>> = const { RefCell::new(Vec::new()) };
+    static CALLERS: RefCell<Vec<Pubkey>> = const { RefCell::new(Vec::new()) };
+    static CLOCK: RefCell<i64> = const { RefCell::new(NOW) };
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct Account {
+    pub lamports: u64,
+    pub data: Vec<u8>,
+    pub owner: Pubkey,
+    pub executable: bool,
+}
+
+pub struct Runtime {
+    accounts: HashMap<Pubkey, Account>,
+    programs: Vec<(Pubkey, Entry)>,
+}
+
+impl Runtime {
+    /// Builds a runtime that can run and be called into by `programs`
+    pub fn new(programs: &[(Pubkey, Entry)]) -> Self {
+        static STUBS: Once = Once::new();
+        STUBS.call_once(|| {
+            set_syscall_stubs(Box::new(Stubs));
+        });
+
+        let mut runtime = Runtime {
+            accounts: HashMap::new(),
+            programs: programs.to_vec(),
+        };
+
+        let program_ids = programs.iter().map(|(program_id, _)| *program_id);
+        for program in [system_program::ID, spl_token::ID].into_iter().chain(program_ids) {
+            runtime.set(program, Account {
+                lamports: 1,
+                data: Vec::new(),
+                owner: bpf_loader::ID,
+                executable: true,
+            });
+        }
+
+        runtime.warp(NOW);
+
+        runtime
+    }
+
+    /// Sets the clock's unix timestamp for the following instructions
+    pub fn warp(&mut self, unix_timestamp: i64) {
+        CLOCK.with(|clock| *clock.borrow_mut() = unix_timestamp);
+    }
+
+    pub fn set(&mut self, key: Pubkey, account: Account) {
+        self.accounts.insert(key, account);
+    }
+
+    pub fn account(&self, key: &Pubkey) -> Option<&Account> {
+        self.accounts.get(key)
+    }
+
+    pub fn fund(&mut self, key: Pubkey) {
+        self.set(key, Account {
+            lamports: 10_000_000_000,
+            data: Vec::new(),
+            owner: system_program::ID,
+            executable: false,
+        });
+    }
+
+    /// Stores an Anchor account owned by `owner`, allocated at exactly `space` bytes
+    pub fn set_state<T: AccountSerialize>(&mut self, key: Pubkey, owner: Pubkey, space: usize, state: &T) {
+        let mut data = Vec::new();
+        state.try_serialize(&mut data).unwrap();
+        data.resize(space, 0);
+
+        self.set(key, Account {
+            lamports: Rent::default().minimum_balance(space),
+            data,
+            owner,
+            executable: false,
+        });
+    }
+
+    pub fn state<T: AccountDeserialize>(&self, key: &Pubkey) -> T {
+        let data = &self.accounts[key].data;
+        T::try_deserialize(&mut &data[..]).unwrap()
+    }
+
+    pub fn set_pack<T: Pack>(&mut self, key: Pubkey, owner: Pubkey, state: T) {
+        let mut data = vec![0; T::LEN];
+        T::pack(state, &mut data).unwrap();
+
+        self.set(key, Account {
+            lamports: Rent::default().minimum_balance(T::LEN),
+            data,
+            owner,
+            executable: false,
+        });
+    }
+
+    pub fn unpack<T: Pack + IsInitialized>(&self, key: &Pubkey) -> T {
+        T::unpack(&self.accounts[key].data).unwrap()
+    }
+
+    /// Runs `instruction`, only keeping its changes if it succeeds
+    pub fn process(&mut self, instruction: Instruction) -> ProgramResult {
+        let entry = self
+            .programs
+            .iter()
+            .find(|(program_id, _)| program_id == &instruction.program_id)
+            .map(|(_, entry)| *entry)
+            .ok_or(ProgramError::IncorrectProgramId)?;
+
+        let mut input = serialize(&self.accounts, &instruction);
+
+        // Safety: `input` is laid out like the loader's aligned input and outlives `infos`
+        let (program_id, infos, data) = unsafe { deserialize(input.as_mut_ptr() as *mut u8) };
+
+        PROGRAMS.with(|programs| *programs.borrow_mut() = self.programs.clone());
+        CALLERS.with(|callers| *callers.borrow_mut() = vec![*program_id]);
+        entry(program_id, &infos, data)?;
+
+        for info in infos {
+            self.accounts.insert(*info.key, Account {
+                lamports: info.lamports(),
+                data: info.data.borrow().to_vec(),
+                owner: *info.owner,
+                executable: info.executable,
+            });
+        }
+
+        Ok(())
+    }
+}
+
+/// Lays out accounts and instruction data like the BPF loader's aligned input
+fn serialize(accounts: &HashMap<Pubkey, Account>, instruction: &Instruction) -> Vec<u64> {
+    let mut input: Vec<u8> = Vec::new();
+    input.extend_from_slice(&(instruction.accounts.len() as u64).to_le_bytes());
+
+    for (i, meta) in instruction.accounts.iter().enumerate() {
+        if let Some(first) = instruction.accounts[..i].iter().position(|other| other.pubkey == meta.pubkey) {
+            input.push(first as u8);
+            input.extend_from_slice(&[0; 7]);
+            continue
+        }
+
+        // The runtime merges the privileges of every meta naming the same key
+        let metas = || instruction.accounts.iter().filter(|other| other.pubkey == meta.pubkey);
+        let account = accounts.get(&meta.pubkey).cloned().unwrap_or_default();
+        input.push(NON_DUP_MARKER);
+        input.push(metas().any(|other| other.is_signer) as u8);
+        input.push(metas().any(|other| other.is_writable) as u8);
+        input.push(account.executable as u8);
+        input.extend_from_slice(&[0; 4]);
+        input.extend_from_slice(meta.pubkey.as_ref());
+        input.extend_from_slice(account.owner.as_ref());
+        input.extend_from_slice(&account.lamports.to_le_bytes());
+        input.extend_from_slice(&(account.data.len() as u64).to_le_bytes());
+        input.extend_from_slice(&account.data);
+        input.resize(input.len() + MAX_PERMITTED_DATA_INCREASE, 0);
+        input.resize(input.len().div_ceil(8) * 8, 0);
+        input.extend_from_slice(&0u64.to_le_bytes());
+    }
+
+    input.extend_from_slice(&(instruction.data.len() as u64).to_le_bytes());
+    input.extend_from_slice(&instruction.data);
+    input.extend_from_slice(instruction.program_id.as_ref());
+
+    // Backed by u64s so the layout's 8-byte alignment holds
+    let mut aligned = vec![0u64; input.len().div_ceil(8)];
+    unsafe {
+        std::ptr::copy_nonoverlapping(input.as_ptr(), aligned.as_mut_ptr() as *mut u8, input.len());
+    }
+
+    aligned
+}
+
+struct Stubs;
+
+impl SyscallStubs for Stubs {
+    fn sol_log(&self, _message: &str) {}
+
+    fn sol_log_data(&self, _fields: &[&[u8]]) {}
+
+    fn sol_get_clock_sysvar(&self, var_addr: *mut u8) -> u64 {
+        let unix_timestamp = CLOCK.with(|clock| *clock.borrow());
+        unsafe {
+            *(var_addr as *mut Clock) = Clock {
+                unix_timestamp,
+                ..Clock::default()
+            };
+        }
+
+        SUCCESS
+    }
+
+    fn sol_get_rent_sysvar(&self, var_addr: *mut u8) -> u64 {
+        unsafe {
+            *(var_addr as *mut Rent) = Rent::default();
+        }
+
+        SUCCESS
+    }
+
+    fn sol_invoke_signed(
+        &self,
+        instruction: &Instruction,
+        account_infos: &[AccountInfo],
+        signers_seeds: &[&[&[u8]]],
+    ) -> ProgramResult {
+        let caller = CALLERS.with(|callers| *callers.borrow().last().unwrap());
+        let pda_signers = signers_seeds
+            .iter()
+            .map(|seeds| Pubkey::create_program_address(seeds, &caller))
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+
+        let mut infos = Vec::new();
+        for meta in &instruction.accounts {
+            let mut info = account_infos
+                .iter()
+                .find(|info| info.key == &meta.pubkey)
+                .cloned()
+                .ok_or(ProgramError::NotEnoughAccountKeys)?;
+
+            if meta.is_signer && !info.is_signer && !pda_signers.contains(&meta.pubkey) {
+                return Err(ProgramError::MissingRequiredSignature)
+            }
+
+            if meta.is_writable && !info.is_writable {
+                return Err(ProgramError::InvalidArgument)
+            }
+
+            info.is_signer = meta.is_signer;
+            info.is_writable = meta.is_writable;
+            infos.push(info);
+        }
+
+        if instruction.program_id == system_program::ID {
+            return system(&infos, &instruction.data)
+        }
+
+        if instruction.program_id == spl_token::ID {
+            return spl_token::processor::Processor::process(&instruction.program_id, &infos, &instruction.data)
+        }
+
+        let entry = PROGRAMS.with(|programs| {
+            programs
+                .borrow()
+                .iter()
+                .find(|(program_id, _)| program_id == &instruction.program_id)
+                .map(|(_, entry)| *entry)
+        });
+        let entry = entry.ok_or(ProgramError::IncorrectProgramId)?;
+
+        CALLERS.with(|callers| callers.borrow_mut().push(instruction.program_id));
+        let result = entry(&instruction.program_id, &infos, &instruction.data);
+        CALLERS.with(|callers| callers.borrow_mut().pop());
+
+        result
+    }
+}
+
+fn system(infos: &[AccountInfo], data: &[u8]) -> ProgramResult {
+    let transfer = |from: &AccountInfo, to: &AccountInfo, lamports: u64| -> ProgramResult {
+        if !from.is_signer {
+            return Err(ProgramError::MissingRequiredSignature)
+        }
+
+        let balance = from.lamports().checked_sub(lamports).ok_or(ProgramError::InsufficientFunds)?;
+        **from.try_borrow_mut_lamports()? = balance;
+        **to.try_borrow_mut_lamports()? += lamports;
+
+        Ok(())
+    };
+
+    match limited_deserialize::<SystemInstruction>(data, 1232).map_err(|_| ProgramError::InvalidInstructionData)? {
+        SystemInstruction::CreateAccount { lamports, space, owner } => {
+            let (from, to) = (&infos[0], &infos[1]);
+            if to.lamports() != 0 || !to.data_is_empty() || !to.is_signer {
+                return Err(ProgramError::AccountAlreadyInitialized)
+            }
+
+            transfer(from, to, lamports)?;
+            to.realloc(space as usize, true)?;
+            to.assign(&owner);
+        }
+        SystemInstruction::Transfer { lamports } => transfer(&infos[0], &infos[1], lamports)?,
+        SystemInstruction::Allocate { space } => infos[0].realloc(space as usize, true)?,
+        SystemInstruction::Assign { owner } => infos[0].assign(&owner),
+        _ => return Err(ProgramError::InvalidInstructionData),
+    }
+
+    Ok(())
+}
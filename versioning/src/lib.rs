@@ -0,0 +1,86 @@
+//! Rent and realloc helpers behind each program's `migrate`, see
+//! `members::CURRENT_VERSION` for the versioning policy.
+
+// Anchor's error type is what the programs return, however large
+#![allow(clippy::result_large_err)]
+
+use anchor_lang::prelude::*;
+use anchor_lang::system_program::{self, Transfer};
+
+/// How `migrate` brings an account to the current layout
+pub enum Upgrade {
+    /// Unversioned account, rewritten in full from its legacy layout
+    Rewrite(Vec<u8>),
+    /// Versioned account at the given version, which only gets a zeroed tail
+    Grow(u8),
+}
+
+impl Upgrade {
+    /// Rewrites an unversioned account as `state`
+    pub fn rewrite<T: AccountSerialize>(state: &T) -> Result<Self> {
+        let mut data = Vec::new();
+        state.try_serialize(&mut data)?;
+
+        Ok(Upgrade::Rewrite(data))
+    }
+
+    pub fn from_version(&self) -> u8 {
+        match self {
+            Upgrade::Rewrite(_) => 0,
+            Upgrade::Grow(version) => *version,
+        }
+    }
+}
+
+/// Reallocs `account` to `space` and writes `upgrade` at `version`
+pub fn apply<'info>(
+    account: &AccountInfo<'info>,
+    payer: &AccountInfo<'info>,
+    system_program: &AccountInfo<'info>,
+    upgrade: Upgrade,
+    space: usize,
+    version: u8
+) -> Result<()> {
+    top_up_rent(account, payer, system_program, space)?;
+
+    if account.data_len() != space {
+        account.realloc(space, true)?;
+    }
+
+    let mut data = account.try_borrow_mut_data()?;
+    match upgrade {
+        Upgrade::Rewrite(rewritten) => {
+            // Shrinking keeps the legacy bytes, so clear the tail later fields will read
+            data[..rewritten.len()].copy_from_slice(&rewritten);
+            data[rewritten.len()..].fill(0);
+        }
+        Upgrade::Grow(_) => data[8] = version,
+    }
+
+    Ok(())
+}
+
+/// Funds `account` up to rent exemption at `space` bytes
+pub fn top_up_rent<'info>(
+    account: &AccountInfo<'info>,
+    payer: &AccountInfo<'info>,
+    system_program: &AccountInfo<'info>,
+    space: usize
+) -> Result<()> {
+    let required = Rent::get()?.minimum_balance(space);
+    let lamports = account.lamports();
+
+    if required > lamports {
+        let cpi_ctx = CpiContext::new(
+            system_program.clone(),
+            Transfer {
+                from: payer.clone(),
+                to: account.clone(),
+            },
+        );
+
+        system_program::transfer(cpi_ctx, required - lamports)?;
+    }
+
+    Ok(())
+}